@@ -0,0 +1,17 @@
+//! `gen_table` on a 500 user by 200 problem board, run with `cargo bench`
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use foj_scoreboard::scoreboard::TableOptions;
+use foj_scoreboard::{Metadata, Scoreboard};
+
+fn gen_table(c: &mut Criterion) {
+    let board = Scoreboard::synthetic(500, 200).unwrap();
+    let meta = Metadata::default();
+    let opts = TableOptions::default();
+    c.bench_function("gen_table 500x200", |b| {
+        b.iter(|| board.gen_table(&meta, &opts))
+    });
+}
+
+criterion_group!(benches, gen_table);
+criterion_main!(benches);
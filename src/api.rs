@@ -1,77 +1,464 @@
 #![allow(dead_code)]
 
 use crate::error::{SimpleError, SimpleResult};
+use crate::meta::mask_token;
 use chrono::prelude::*;
-use futures::future::Future;
+use futures::future::{self, Either, Future};
+use futures::{stream, Stream};
 use reqwest::header;
 use reqwest::header::HeaderMap;
-use reqwest::r#async::Client;
+use reqwest::r#async::{Client, Request, RequestBuilder, Response};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use serde_repr::*;
-use std::time::Duration;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_timer::Delay;
+
+/// The NCTU judge, used unless `base_url` is set in meta.toml
+pub const DEFAULT_BASE_URL: &str = "https://api.oj.nctu.me";
+/// How long a fetched session is trusted before asking the judge again
+const SESSION_TTL: Duration = Duration::from_secs(300);
+/// Ids asked per bulk user request, keeping the URL a sane length
+const USER_BATCH: usize = 100;
+/// Submissions asked per page by default, the judge truncates very large pages
+pub const SUBMISSION_PAGE: usize = 1000;
+/// Submission pages requested at once
+const PAGES_IN_FLIGHT: usize = 4;
+
+/// How many times a failed request is sent again, and how long to wait
+///
+/// Only timeouts, dropped connections and 5xx replies are retried; the
+/// wait doubles after each attempt, with some jitter so tabs don't retry
+/// in lockstep.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never send a request twice
+    pub fn none() -> Self {
+        RetryPolicy {
+            retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// The wait before retry number `attempt`, counting from 0
+    fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.base_delay * 2u32.saturating_pow(attempt.min(16));
+        // Nothing here needs a real RNG, the clock's low bits will do
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let spread = backoff.as_millis() as u64 / 2 + 1;
+        backoff + Duration::from_millis(u64::from(nanos) % spread)
+    }
+}
+
+/// How submission lists are fetched: the rows asked per page, and at most
+/// how many of the newest are kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Paging {
+    pub page_size: usize,
+    /// `None` fetches every submission
+    pub max_submissions: Option<usize>,
+}
+
+impl Default for Paging {
+    fn default() -> Self {
+        Paging {
+            page_size: SUBMISSION_PAGE,
+            max_submissions: None,
+        }
+    }
+}
+
+/// What an interrupted fetch of a submission list had received, so the next
+/// one asks only for the rest
+///
+/// The judge lists the newest first, so new submissions push those already
+/// received further back by as many as the count grew.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FetchCheckpoint {
+    /// The judge's count when `rows` was last moved on
+    reported: usize,
+    /// The newest this many were all received
+    rows: usize,
+    submissions: Vec<Submission>,
+}
+
+impl FetchCheckpoint {
+    /// The last page holding only new submissions, and the page to go on
+    /// from, both for a list now counting `count`
+    ///
+    /// The page holding the oldest received row is asked again, which covers
+    /// a few submissions having been deleted since.
+    fn pages_left(&self, count: usize, page_size: usize) -> (u32, u32) {
+        if self.rows == 0 {
+            return (0, 1);
+        }
+        let grown = count as i64 - self.reported as i64;
+        let top = (grown.max(0) as usize).div_ceil(page_size) as u32;
+        let oldest = (self.rows as i64 + grown).max(1) as usize;
+        (top, ((oldest - 1) / page_size + 1) as u32)
+    }
+
+    /// Keep page `n`, which moves `rows` on for pages from `resume`
+    fn keep(&mut self, n: u32, resume: u32, count: usize, page_size: usize, subs: Vec<Submission>) {
+        if n >= resume {
+            self.reported = count;
+            self.rows = n as usize * page_size;
+        }
+        self.submissions.extend(subs);
+    }
+}
+
+/// A `FetchCheckpoint` shared between a fetch and the board keeping it
+#[derive(Clone, Debug, Default)]
+pub struct Checkpoint(Arc<Mutex<FetchCheckpoint>>);
+
+impl Checkpoint {
+    fn lock(&self) -> MutexGuard<'_, FetchCheckpoint> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Whether a fetch was interrupted with some pages received
+    pub fn is_empty(&self) -> bool {
+        self.lock().rows == 0
+    }
+
+    /// Forget what was received, once it has all been counted
+    pub fn clear(&self) {
+        *self.lock() = FetchCheckpoint::default();
+    }
+}
+
+impl Serialize for Checkpoint {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.lock().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Checkpoint {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        FetchCheckpoint::deserialize(deserializer).map(|c| Checkpoint(Arc::new(Mutex::new(c))))
+    }
+}
+
+/// How the user token is sent to the judge
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthScheme {
+    /// A `token=...` cookie, what the FOJ frontend does
+    #[default]
+    Cookie,
+    /// `Authorization: Bearer ...`
+    Bearer,
+}
 
-#[derive(Debug)]
 pub struct FojApi {
     token: String,
+    client: ApiClient,
+    session_cache: Arc<Mutex<Option<(Session, Instant)>>>,
+    /// Cleared once the judge turns out not to have `/users/?ids=`
+    bulk_users: Arc<AtomicBool>,
+}
+
+// The client's default headers carry the token, so it's left out
+impl fmt::Debug for FojApi {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FojApi")
+            .field("token", &mask_token(&self.token))
+            .finish()
+    }
+}
+
+/// The HTTP client along with the judge it talks to
+#[derive(Clone)]
+struct ApiClient {
     client: Client,
+    /// Without a trailing slash, see `normalize_base_url`
+    base_url: Arc<str>,
+    retry: RetryPolicy,
+    paging: Paging,
+}
+
+impl ApiClient {
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.client.get(&format!("{}/{}", self.base_url, path))
+    }
+
+    /// Send a GET, retrying it as `retry` says
+    ///
+    /// A 4xx like a rejected token is returned right away. Once the retries
+    /// run out, the last reply or error is returned as it is.
+    fn send(&self, builder: RequestBuilder) -> impl Future<Item = Response, Error = SimpleError> {
+        let request = match builder.build() {
+            Ok(request) => request,
+            Err(e) => return Either::A(future::err(e.into())),
+        };
+        let method = request.method().clone();
+        let url = request.url().clone();
+        let headers = request.headers().clone();
+        let client = self.client.clone();
+        let retry = self.retry;
+        Either::B(future::loop_fn(0, move |attempt: u32| {
+            let mut request = Request::new(method.clone(), url.clone());
+            *request.headers_mut() = headers.clone();
+            let url = url.clone();
+            client.execute(request).then(move |res| {
+                let reason = match &res {
+                    Ok(res) if res.status().is_server_error() => Some(res.status().to_string()),
+                    Ok(_) => None,
+                    Err(e) if e.is_timeout() || e.is_http() => Some(e.to_string()),
+                    Err(_) => None,
+                };
+                let reason = match reason {
+                    Some(reason) if attempt < retry.retries => reason,
+                    _ => {
+                        return Either::A(future::result(
+                            res.map(future::Loop::Break).map_err(SimpleError::from),
+                        ))
+                    }
+                };
+                let delay = retry.delay(attempt);
+                warn!(
+                    "GET {} failed ({}), retry {}/{} in {}ms",
+                    url.path(),
+                    reason,
+                    attempt + 1,
+                    retry.retries,
+                    delay.as_millis()
+                );
+                Either::B(
+                    Delay::new(Instant::now() + delay)
+                        .map_err(|e| SimpleError::Custom {
+                            message: format!("Retry timer failed: {}", e),
+                        })
+                        .map(move |_| future::Loop::Continue(attempt + 1)),
+                )
+            })
+        }))
+    }
+}
+
+/// Check `url` is an http(s) URL, and drop any trailing slash
+fn normalize_base_url(url: &str) -> SimpleResult<String> {
+    let trimmed = url.trim().trim_end_matches('/');
+    let invalid = |reason: String| SimpleError::Custom {
+        message: format!("Invalid base_url {}: {}", url, reason),
+    };
+    let parsed = Url::parse(trimmed).map_err(|e| invalid(e.to_string()))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(invalid("only http and https are supported".to_owned()));
+    }
+    if parsed.host_str().is_none() {
+        return Err(invalid("no host".to_owned()));
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err(invalid("it cannot have a query or fragment".to_owned()));
+    }
+    Ok(trimmed.to_owned())
 }
 
 impl FojApi {
-    pub fn new(token: String) -> SimpleResult<Self> {
+    /// Talk to the judge at `base_url`, like `DEFAULT_BASE_URL`
+    pub fn new(token: String, base_url: &str) -> SimpleResult<Self> {
+        Self::with_auth(token, AuthScheme::Cookie, base_url)
+    }
+
+    /// Like `new`, sending the token the way `scheme` says
+    pub fn with_auth(token: String, scheme: AuthScheme, base_url: &str) -> SimpleResult<Self> {
+        let base_url = normalize_base_url(base_url)?;
+        let invalid = |_| SimpleError::Custom {
+            message: format!(
+                "Invalid token {}: it cannot have control characters like newlines",
+                mask_token(&token)
+            ),
+        };
         let mut headers = HeaderMap::new();
-        headers.insert(header::COOKIE, format!("token={}", token).parse().unwrap());
+        match scheme {
+            AuthScheme::Cookie => {
+                let value = format!("token={}", token).parse().map_err(invalid)?;
+                headers.insert(header::COOKIE, value);
+            }
+            AuthScheme::Bearer => {
+                let value = format!("Bearer {}", token).parse().map_err(invalid)?;
+                headers.insert(header::AUTHORIZATION, value);
+            }
+        }
 
         let client = Client::builder()
             .default_headers(headers)
             .connect_timeout(Duration::from_secs(10))
             .build()?;
 
-        Ok(FojApi { token, client })
+        Ok(FojApi {
+            token,
+            client: ApiClient {
+                client,
+                base_url: base_url.into(),
+                retry: RetryPolicy::default(),
+                paging: Paging::default(),
+            },
+            session_cache: Arc::new(Mutex::new(None)),
+            bulk_users: Arc::new(AtomicBool::new(true)),
+        })
+    }
+
+    /// Retry failed requests as `retry` says, instead of `RetryPolicy::default`
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.client.retry = retry;
+        self
+    }
+
+    /// Page submission lists as `paging` says, instead of `Paging::default`
+    pub fn with_paging(mut self, paging: Paging) -> Self {
+        self.client.paging = paging;
+        self
     }
 
     pub fn session(&self) -> impl Future<Item = Session, Error = SimpleError> {
-        self.client
-            .get("https://api.oj.nctu.me/session/")
-            .send()
-            .and_then(|res| res.error_for_status())
-            .and_then(|mut res| res.json())
-            .map_err(|e| e.into())
-            .and_then(|msg: Msg<Session>| Ok(msg.unwrap()))
+        if let Some((session, fetched_at)) = &*self
+            .session_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+        {
+            if fetched_at.elapsed() < SESSION_TTL {
+                return Either::A(future::ok(session.clone()));
+            }
+        }
+        let cache = self.session_cache.clone();
+        Either::B(
+            self.client
+                .send(self.client.get("session/"))
+                .and_then(read_json)
+                .and_then(move |msg: Msg<Session>| {
+                    let session = msg.unwrap();
+                    *cache.lock().unwrap_or_else(PoisonError::into_inner) =
+                        Some((session.clone(), Instant::now()));
+                    Ok(session)
+                }),
+        )
+    }
+
+    /// Forget the cached session, e.g. when the token is rejected
+    pub fn invalidate_session(&self) {
+        *self
+            .session_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = None;
     }
 
     pub fn get_problem_list(
         &self,
         group_id: u32,
     ) -> impl Future<Item = Vec<Problem>, Error = SimpleError> {
-        self.client
-            .get(format!("https://api.oj.nctu.me/groups/{}/problems/", group_id).as_str())
+        let builder = self
+            .client
+            .get(&format!("groups/{}/problems/", group_id))
             .query(&[("group_id", group_id.to_string())])
             .query(&[("count", 10000.to_string())])
-            .query(&[("page", 1.to_string())])
-            .send()
-            .and_then(|res| res.error_for_status())
-            .and_then(|mut res| res.json())
-            .map_err(|e| e.into())
+            .query(&[("page", 1.to_string())]);
+        self.client
+            .send(builder)
+            .and_then(read_json)
             .map(|msg: Msg<ProblemList>| msg.unwrap().data)
     }
 
+    /// Every group the token can see
+    pub fn get_groups(&self) -> impl Future<Item = Vec<Group>, Error = SimpleError> {
+        let builder = self
+            .client
+            .get("groups/")
+            .query(&[("count", 10000.to_string())])
+            .query(&[("page", 1.to_string())]);
+        self.client
+            .send(builder)
+            .and_then(read_json)
+            .map(|msg: Msg<GroupList>| msg.unwrap().data)
+    }
+
     pub fn get_submission_group(
         &self,
         group_id: u32,
     ) -> impl Future<Item = Vec<Submission>, Error = SimpleError> {
-        self.get_submission(group_id, 1_000_000, 1, None, None, None)
+        self.get_submission_group_counted(group_id, Checkpoint::default())
             .map(|res: (usize, Vec<Submission>)| res.1)
     }
 
+    /// Like `get_submission_group`, along with the total the judge reported
+    ///
+    /// Goes on from `checkpoint` when an earlier fetch was interrupted.
+    pub fn get_submission_group_counted(
+        &self,
+        group_id: u32,
+        checkpoint: Checkpoint,
+    ) -> impl Future<Item = (usize, Vec<Submission>), Error = SimpleError> {
+        fetch_all_submissions(
+            self.client.clone(),
+            Scope::Group(group_id),
+            None,
+            checkpoint,
+        )
+    }
+
     pub fn get_submission_prob(
         &self,
         group_id: u32,
         pid: u32,
     ) -> impl Future<Item = Vec<Submission>, Error = SimpleError> {
-        self.get_submission(group_id, 1_000_000, 1, Some(pid), None, None)
+        self.get_submission_prob_counted(group_id, pid, Checkpoint::default())
             .map(|res: (usize, Vec<Submission>)| res.1)
     }
 
+    /// Like `get_submission_prob`, going on from `checkpoint` as well
+    pub fn get_submission_prob_counted(
+        &self,
+        group_id: u32,
+        pid: u32,
+        checkpoint: Checkpoint,
+    ) -> impl Future<Item = (usize, Vec<Submission>), Error = SimpleError> {
+        let scope = Scope::Group(group_id);
+        fetch_all_submissions(self.client.clone(), scope, Some(pid), checkpoint)
+    }
+
+    /// Submissions made within a contest, which has its own scope apart from groups
+    pub fn get_submission_contest(
+        &self,
+        contest_id: u32,
+    ) -> impl Future<Item = Vec<Submission>, Error = SimpleError> {
+        self.get_submission_contest_counted(contest_id, Checkpoint::default())
+            .map(|res: (usize, Vec<Submission>)| res.1)
+    }
+
+    /// Like `get_submission_contest`, going on from `checkpoint` as well
+    pub fn get_submission_contest_counted(
+        &self,
+        contest_id: u32,
+        checkpoint: Checkpoint,
+    ) -> impl Future<Item = (usize, Vec<Submission>), Error = SimpleError> {
+        let scope = Scope::Contest(contest_id);
+        fetch_all_submissions(self.client.clone(), scope, None, checkpoint)
+    }
+
     fn get_submission(
         &self,
         group_id: u32,
@@ -81,38 +468,259 @@ impl FojApi {
         name: Option<&str>,
         verdict: Option<Verdict>,
     ) -> impl Future<Item = (usize, Vec<Submission>), Error = SimpleError> {
-        let mut builder = self
-            .client
-            .get("https://api.oj.nctu.me/submissions/")
-            .query(&[("group_id", group_id.to_string())])
-            .query(&[("count", count.to_string())])
-            .query(&[("page", page.to_string())]);
-        if let Some(pid) = pid {
-            builder = builder.query(&[("problem_id", pid.to_string())])
-        }
-        if let Some(name) = name {
-            builder = builder.query(&[("name", name)])
-        }
-        if let Some(verdict) = verdict {
-            builder = builder.query(&[("verdict_id", (verdict as u32).to_string())])
-        }
-        builder
-            .send()
-            .and_then(|res| res.error_for_status())
-            .and_then(|mut res| res.json())
-            .map_err(|e| e.into())
-            .and_then(|msg: Msg<SubmissionList>| Ok((msg.msg.count as usize, msg.msg.submissions)))
+        let scope = Scope::Group(group_id);
+        fetch_submissions(&self.client, scope, count, page, pid, name, verdict)
     }
 
     pub fn get_user_name(&self, user_id: u32) -> impl Future<Item = String, Error = SimpleError> {
-        self.client
-            .get(format!("https://api.oj.nctu.me/users/{}/", user_id).as_str())
-            .send()
-            .and_then(|res| res.error_for_status())
-            .and_then(|mut res| res.json())
-            .map_err(|e| e.into())
-            .and_then(|msg: Msg<UserName>| Ok(msg.unwrap().name))
+        fetch_user_name(&self.client, user_id)
+    }
+
+    /// Look up many names with a request per batch of ids
+    ///
+    /// Judges without the bulk endpoint get one request per user instead,
+    /// and aren't asked for it again. Ids the judge doesn't know are left out.
+    pub fn get_user_names(
+        &self,
+        ids: &[u32],
+    ) -> impl Future<Item = BTreeMap<u32, String>, Error = SimpleError> {
+        let batches: Vec<_> = ids
+            .chunks(USER_BATCH)
+            .map(|batch| {
+                let client = self.client.clone();
+                let bulk = self.bulk_users.clone();
+                let batch = batch.to_vec();
+                let each = move |client: ApiClient, batch: Vec<u32>| {
+                    let names = batch
+                        .into_iter()
+                        .map(move |uid| fetch_user_name(&client, uid).map(move |name| (uid, name)));
+                    future::join_all(names).map(|names| names.into_iter().collect())
+                };
+                if !bulk.load(Ordering::SeqCst) {
+                    return Either::A(each(client, batch));
+                }
+                let ids: Vec<String> = batch.iter().map(|uid| uid.to_string()).collect();
+                let builder = self.client.get("users/").query(&[("ids", ids.join(","))]);
+                Either::B(
+                    self.client
+                        .send(builder)
+                        .and_then(read_json)
+                        .map(|msg: Msg<Vec<UserName>>| {
+                            msg.unwrap()
+                                .into_iter()
+                                .map(|user| (user.id as u32, user.name))
+                                .collect::<BTreeMap<_, _>>()
+                        })
+                        .or_else(move |e| match e {
+                            SimpleError::Unavailable => Either::A(future::err(e)),
+                            _ if e.is_unauthorized() => Either::A(future::err(e)),
+                            _ => {
+                                debug!("No bulk user lookup ({}), asking one by one", e);
+                                bulk.store(false, Ordering::SeqCst);
+                                Either::B(each(client, batch))
+                            }
+                        }),
+                )
+            })
+            .collect();
+        future::join_all(batches).map(|maps| maps.into_iter().flatten().collect())
+    }
+}
+
+/// Where submissions are listed from, a contest has its own list apart from groups
+#[derive(Clone, Copy, Debug)]
+enum Scope {
+    Group(u32),
+    Contest(u32),
+}
+
+fn fetch_submissions(
+    client: &ApiClient,
+    scope: Scope,
+    count: usize,
+    page: u32,
+    pid: Option<u32>,
+    name: Option<&str>,
+    verdict: Option<Verdict>,
+) -> impl Future<Item = (usize, Vec<Submission>), Error = SimpleError> {
+    let mut builder = match scope {
+        Scope::Group(group_id) => client
+            .get("submissions/")
+            .query(&[("group_id", group_id.to_string())]),
+        Scope::Contest(contest_id) => client.get(&format!("contests/{}/submissions/", contest_id)),
+    };
+    builder = builder
+        .query(&[("count", count.to_string())])
+        .query(&[("page", page.to_string())]);
+    if let Some(pid) = pid {
+        builder = builder.query(&[("problem_id", pid.to_string())])
+    }
+    if let Some(name) = name {
+        builder = builder.query(&[("name", name)])
+    }
+    if let Some(verdict) = verdict {
+        builder = builder.query(&[("verdict_id", verdict.id().to_string())])
+    }
+    client
+        .send(builder)
+        .and_then(read_json)
+        .and_then(|msg: Msg<SubmissionList>| Ok((msg.msg.count, msg.msg.submissions)))
+}
+
+/// Every page of submissions, along with the total the judge reported
+///
+/// The pages the first one's count asks for are fetched concurrently. While
+/// the last page comes back full, the next one is asked as well, which
+/// catches submissions made meanwhile pushing the oldest further back. Those
+/// also repeat a submission across pages, so duplicates are dropped.
+///
+/// With `Paging::max_submissions` set, only that many of the newest are
+/// fetched; the judge lists them newest first.
+///
+/// Pages are kept in `checkpoint` as they come in. One left by an
+/// interrupted fetch is picked up, asking only for the newer submissions and
+/// those past the rows it holds.
+fn fetch_all_submissions(
+    client: ApiClient,
+    scope: Scope,
+    pid: Option<u32>,
+    checkpoint: Checkpoint,
+) -> impl Future<Item = (usize, Vec<Submission>), Error = SimpleError> {
+    let paging = client.paging;
+    let page =
+        move |n: u32| fetch_submissions(&client, scope, paging.page_size, n, pid, None, None);
+    collect_pages(page, paging, checkpoint)
+}
+
+/// `fetch_all_submissions` over any source of pages, counting from 1
+fn collect_pages<P, F>(
+    page: P,
+    paging: Paging,
+    checkpoint: Checkpoint,
+) -> impl Future<Item = (usize, Vec<Submission>), Error = SimpleError>
+where
+    P: Fn(u32) -> F + Clone,
+    F: Future<Item = (usize, Vec<Submission>), Error = SimpleError>,
+{
+    let Paging {
+        page_size,
+        max_submissions,
+    } = paging;
+    let more = page.clone();
+    let kept = checkpoint.clone();
+    page(1)
+        .and_then(move |(count, first)| {
+            let cap = max_submissions.filter(|&max| max < count);
+            if let Some(max) = cap {
+                warn!(
+                    "Fetching only the newest {} of {} submissions, see max_submissions",
+                    max, count
+                );
+            }
+            let rows = cap.unwrap_or(count);
+            let pages = rows.div_ceil(page_size).max(1) as u32;
+            let (top, resume) = checkpoint.lock().pages_left(count, page_size);
+            if resume > 1 {
+                info!("Resuming an interrupted fetch from page {}", resume);
+            }
+            let full = if resume <= 1 { first.len() } else { 0 };
+            checkpoint.lock().keep(1, resume, count, page_size, first);
+            let asked: Vec<u32> = (2..=top.min(pages))
+                .chain(resume.max(top + 1).max(2)..=pages)
+                .collect();
+            // Nothing is missing when the held rows already reach the end
+            let last = asked.last().copied().filter(|&n| n >= resume).unwrap_or(1);
+            let done = checkpoint.clone();
+            // A failed page would otherwise end the stream before the pages
+            // ahead of it, which came back fine, are kept
+            stream::iter_ok(asked)
+                .map(move |n| more(n).then(move |res| Ok((n, res))))
+                .buffered(PAGES_IN_FLIGHT)
+                .fold(full, move |full, (n, res): (u32, SimpleResult<_>)| {
+                    let (_, subs): (usize, Vec<Submission>) = res?;
+                    let full = if n == last { subs.len() } else { full };
+                    done.lock().keep(n, resume, count, page_size, subs);
+                    Ok::<_, SimpleError>(full)
+                })
+                .map(move |full| (count, cap, last, full >= page_size))
+        })
+        .and_then(move |(count, cap, last, full)| {
+            // Past the cap, those pushed back were meant to be left out
+            let full = cap.is_none() && full;
+            let done = kept.clone();
+            let tail = future::loop_fn((last, full), move |(last, full): (u32, bool)| {
+                if !full {
+                    return Either::A(future::ok(future::Loop::Break(())));
+                }
+                let done = done.clone();
+                Either::B(page(last + 1).map(move |(_, subs)| {
+                    let full = subs.len() >= page_size;
+                    done.lock().keep(last + 1, 1, count, page_size, subs);
+                    future::Loop::Continue((last + 1, full))
+                }))
+            });
+            tail.map(move |()| {
+                let mut seen = BTreeSet::new();
+                let mut subs: Vec<Submission> = kept
+                    .lock()
+                    .submissions
+                    .iter()
+                    .filter(|sub| seen.insert(sub.id))
+                    .cloned()
+                    .collect();
+                if let Some(max) = cap {
+                    subs.sort_by_key(|sub| Reverse(sub.id));
+                    subs.truncate(max);
+                }
+                (count, subs)
+            })
+        })
+}
+
+fn fetch_user_name(
+    client: &ApiClient,
+    user_id: u32,
+) -> impl Future<Item = String, Error = SimpleError> {
+    client
+        .send(client.get(&format!("users/{}/", user_id)))
+        .and_then(read_json)
+        .and_then(|msg: Msg<UserName>| Ok(msg.unwrap().name))
+}
+
+/// Parse a reply, telling the judge's HTML maintenance page apart from broken JSON
+fn read_json<T: DeserializeOwned>(res: Response) -> impl Future<Item = T, Error = SimpleError> {
+    let html = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/html"));
+    // A rejected token should still be reported as such
+    let auth = matches!(
+        res.status(),
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN
+    );
+    if html && !auth {
+        return Either::A(future::err(SimpleError::Unavailable));
     }
+    let res = match res.error_for_status() {
+        Ok(res) => res,
+        Err(e) => return Either::A(future::err(e.into())),
+    };
+    Either::B(
+        res.into_body()
+            .concat2()
+            .map_err(SimpleError::from)
+            .and_then(|body| {
+                serde_json::from_slice(&body).map_err(|e| {
+                    // Some pages come without a content type
+                    if body.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'<') {
+                        SimpleError::Unavailable
+                    } else {
+                        e.into()
+                    }
+                })
+            }),
+    )
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -134,22 +742,92 @@ struct ProblemList {
     data: Vec<Problem>,
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize_repr, Serialize_repr)]
-#[repr(u32)]
+#[derive(Debug, Serialize, Deserialize)]
+struct GroupList {
+    count: usize,
+    data: Vec<Group>,
+}
+
+/// Sent and stored as its id
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Verdict {
-    Pending = 1,
-    Judging = 2,
-    SE = 3,
-    CE = 4,
-    RE = 5,
-    MLE = 6,
-    TLE = 7,
-    OLE = 8,
-    WA = 9,
-    AC = 10,
+    Pending,
+    Judging,
+    SE,
+    CE,
+    RE,
+    MLE,
+    TLE,
+    OLE,
+    WA,
+    AC,
+    /// A judge specific one, like an accepted by special judge
+    Other(u32),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Verdict {
+    pub const fn id(self) -> u32 {
+        match self {
+            Verdict::Pending => 1,
+            Verdict::Judging => 2,
+            Verdict::SE => 3,
+            Verdict::CE => 4,
+            Verdict::RE => 5,
+            Verdict::MLE => 6,
+            Verdict::TLE => 7,
+            Verdict::OLE => 8,
+            Verdict::WA => 9,
+            Verdict::AC => 10,
+            Verdict::Other(id) => id,
+        }
+    }
+
+    pub fn from_id(id: u32) -> Self {
+        match id {
+            1 => Verdict::Pending,
+            2 => Verdict::Judging,
+            3 => Verdict::SE,
+            4 => Verdict::CE,
+            5 => Verdict::RE,
+            6 => Verdict::MLE,
+            7 => Verdict::TLE,
+            8 => Verdict::OLE,
+            9 => Verdict::WA,
+            10 => Verdict::AC,
+            id => Verdict::Other(id),
+        }
+    }
+
+    pub fn abbr(self) -> &'static str {
+        match self {
+            Verdict::Pending => "Pending",
+            Verdict::Judging => "Judging",
+            Verdict::SE => "SE",
+            Verdict::CE => "CE",
+            Verdict::RE => "RE",
+            Verdict::MLE => "MLE",
+            Verdict::TLE => "TLE",
+            Verdict::OLE => "OLE",
+            Verdict::WA => "WA",
+            Verdict::AC => "AC",
+            Verdict::Other(_) => "Other",
+        }
+    }
+}
+
+impl Serialize for Verdict {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(self.id())
+    }
+}
+
+impl<'de> Deserialize<'de> for Verdict {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(Verdict::from_id)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Submission {
     pub memory_usage: Option<u64>,
     pub time_usage: Option<u64>,
@@ -166,7 +844,7 @@ pub struct Submission {
     pub score: Option<i32>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Problem {
     pub id: u32,
     pub status: i32,
@@ -178,6 +856,12 @@ pub struct Problem {
     pub group_write: bool,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Group {
+    pub id: u32,
+    pub name: String,
+}
+
 // This module is modified from serde's example
 // See https://serde.rs/custom-date-format.html
 mod simple_datetime {
@@ -221,3 +905,157 @@ impl<M> Msg<M> {
         self.msg
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sub(id: u64) -> Submission {
+        let time = Local.timestamp(1_600_000_000 + id as i64, 0);
+        Submission {
+            memory_usage: None,
+            time_usage: None,
+            length: 0,
+            verdict_id: Verdict::AC,
+            execute_id: 0,
+            user_id: 1,
+            problem_id: 1,
+            created_at: time,
+            updated_at: time,
+            id,
+            score: None,
+        }
+    }
+
+    /// A judge listing `ids` newest first, which calls `between` before
+    /// serving each page, failing it when that does
+    fn judge(
+        ids: Arc<Mutex<Vec<u64>>>,
+        page_size: usize,
+        between: impl Fn(u32, &mut Vec<u64>) -> SimpleResult<()>,
+    ) -> impl Fn(u32) -> future::FutureResult<(usize, Vec<Submission>), SimpleError> + Clone {
+        let between = Arc::new(between);
+        move |n: u32| {
+            let mut ids = ids.lock().unwrap();
+            if let Err(e) = between(n, &mut ids) {
+                return future::err(e);
+            }
+            let page = ids
+                .iter()
+                .rev()
+                .skip((n as usize - 1) * page_size)
+                .take(page_size)
+                .map(|&id| sub(id))
+                .collect();
+            future::ok((ids.len(), page))
+        }
+    }
+
+    fn listed(ids: impl Iterator<Item = u64>) -> Arc<Mutex<Vec<u64>>> {
+        Arc::new(Mutex::new(ids.collect()))
+    }
+
+    fn ids(subs: &[Submission]) -> Vec<u64> {
+        let mut ids: Vec<u64> = subs.iter().map(|sub| sub.id).collect();
+        ids.sort();
+        ids
+    }
+
+    fn paging(page_size: usize) -> Paging {
+        Paging {
+            page_size,
+            max_submissions: None,
+        }
+    }
+
+    #[test]
+    fn judge_specific_verdicts_keep_their_id() {
+        let mut special = sub(1);
+        special.verdict_id = Verdict::Other(12);
+        let json = serde_json::to_string(&special).unwrap();
+        assert!(json.contains("\"verdict_id\":12"));
+        let parsed: Submission = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.verdict_id, Verdict::Other(12));
+
+        let wa = serde_json::to_string(&Verdict::WA).unwrap();
+        assert_eq!(wa, "9");
+        assert_eq!(serde_json::from_str::<Verdict>(&wa).unwrap(), Verdict::WA);
+    }
+
+    #[test]
+    fn pages_survive_a_mid_pagination_insertion() {
+        // Six submissions arrive right after the first page was served,
+        // pushing the oldest one past the pages the first count asked for
+        let page = judge(listed(1..=25), 10, |n, ids| {
+            if n == 2 {
+                ids.extend(26..=31);
+            }
+            Ok(())
+        });
+        let (count, subs) = collect_pages(page, paging(10), Checkpoint::default())
+            .wait()
+            .unwrap();
+        assert_eq!(count, 25);
+        let got = ids(&subs);
+        assert_eq!(got.len(), subs.len(), "a submission came back twice");
+        for id in 1..=25 {
+            assert!(got.contains(&id), "submission {} was lost", id);
+        }
+    }
+
+    #[test]
+    fn pages_stop_at_max_submissions() {
+        let page = judge(listed(1..=25), 10, |_, _| Ok(()));
+        let capped = Paging {
+            page_size: 10,
+            max_submissions: Some(12),
+        };
+        let (count, subs) = collect_pages(page, capped, Checkpoint::default())
+            .wait()
+            .unwrap();
+        assert_eq!(count, 25);
+        assert_eq!(ids(&subs), (14..=25).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn interrupted_fetch_resumes_without_loss_or_duplicates() {
+        let judged = listed(1..=45);
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let log = asked.clone();
+        // The connection drops while asking the fourth page
+        let page = judge(judged.clone(), 10, move |n, _| {
+            log.lock().unwrap().push(n);
+            if n == 4 {
+                return Err(SimpleError::Unavailable);
+            }
+            Ok(())
+        });
+        let checkpoint = Checkpoint::default();
+        assert!(collect_pages(page, paging(10), checkpoint.clone())
+            .wait()
+            .is_err());
+        assert!(!checkpoint.is_empty());
+
+        // The next run loads the checkpoint from the cache, and 12 more
+        // submissions were made meanwhile
+        let bytes = bincode::serialize(&checkpoint).unwrap();
+        let checkpoint: Checkpoint = bincode::deserialize(&bytes).unwrap();
+        judged.lock().unwrap().extend(46..=57);
+        let log = asked.clone();
+        let page = judge(judged, 10, move |n, _| {
+            log.lock().unwrap().push(n);
+            Ok(())
+        });
+        asked.lock().unwrap().clear();
+        let (count, subs) = collect_pages(page, paging(10), checkpoint.clone())
+            .wait()
+            .unwrap();
+        assert_eq!(count, 57);
+        assert_eq!(ids(&subs), (1..=57).collect::<Vec<_>>());
+        // Only the new ones and what was left are asked, from the page
+        // holding the oldest received one
+        let mut asked = asked.lock().unwrap().clone();
+        asked.sort();
+        assert_eq!(asked, vec![1, 2, 5, 6]);
+    }
+}
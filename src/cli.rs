@@ -0,0 +1,330 @@
+use chrono::{DateTime, Local};
+use foj_scoreboard::scoreboard::is_std_stream;
+use foj_scoreboard::{Metadata, ReportFormat, SimpleError, SimpleResult, TimeWindow};
+use log::LevelFilter;
+use std::path::{Path, PathBuf};
+
+const CACHE_FILE: &str = "scoreboard.cache";
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Overrides the meta.toml search, see `Metadata::load`
+    pub config: Option<PathBuf>,
+    /// More meta.toml files, each shown as another board in the TUI
+    pub tabs: Vec<PathBuf>,
+    pub history_dir: Option<PathBuf>,
+    pub offline: bool,
+    /// Start from an empty board instead of the cache
+    pub rebuild: bool,
+    pub dump_path: Option<PathBuf>,
+    /// Only load the cache and report what is wrong with it
+    pub check_cache: bool,
+    /// Print the groups the token can see, to help filling in `group_id`
+    pub list_groups: bool,
+    pub cache: Option<PathBuf>,
+    pub export: Option<ExportFormat>,
+    /// Steps above (`-v`) or below (`-q`) the default log level
+    pub verbosity: i8,
+    /// Only follow this problem, see `Scoreboard::gen_problem_table`
+    pub problem: Option<u32>,
+    pub problems_from_api: bool,
+    pub user_report: Option<u32>,
+    /// Print `Scoreboard::coverage_summary` instead of the board
+    pub summary: bool,
+    pub report_format: Option<ReportFormat>,
+    /// See `Scoreboard::export_public`
+    pub public_export: Option<ReportFormat>,
+    pub pseudonym_map: Option<PathBuf>,
+}
+
+/// Formats written to stdout instead of launching the TUI
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Ansi,
+    /// One row per user, see `Scoreboard::export_csv`
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub const ALL: &'static [ExportFormat] =
+        &[ExportFormat::Ansi, ExportFormat::Csv, ExportFormat::Json];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Ansi => "ANSI text",
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Json => "JSON",
+        }
+    }
+
+    /// File extension used when exporting from the TUI
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Ansi => "ans",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    fn parse(s: &str) -> SimpleResult<Self> {
+        match s {
+            "ansi" => Ok(ExportFormat::Ansi),
+            "csv" => Ok(ExportFormat::Csv),
+            "json" => Ok(ExportFormat::Json),
+            _ => Err(SimpleError::Custom {
+                message: format!("Unknown export format: {}", s),
+            }),
+        }
+    }
+}
+
+impl Options {
+    pub fn parse() -> SimpleResult<Self> {
+        let opts = Self::parse_from(std::env::args().skip(1))?;
+        opts.check()?;
+        Ok(opts)
+    }
+
+    /// Reject flags which cannot be used together
+    fn check(&self) -> SimpleResult<()> {
+        if self.offline && self.rebuild {
+            return Err(SimpleError::Custom {
+                message: "--rebuild needs to fetch, it cannot be used with --offline".to_owned(),
+            });
+        }
+        let printing = self.export.is_some()
+            || self.public_export.is_some()
+            || self.user_report.is_some()
+            || self.summary
+            || self.check_cache
+            || self.dump_path.is_some()
+            || self.list_groups;
+        if !self.tabs.is_empty() && printing {
+            return Err(SimpleError::Custom {
+                message: "--tab only applies to the TUI".to_owned(),
+            });
+        }
+        if !self.tabs.is_empty() && self.cache.is_some() {
+            return Err(SimpleError::Custom {
+                message: "--cache names a single board, it cannot be used with --tab".to_owned(),
+            });
+        }
+        // Its watermark only covers one problem, a full sync would skip the rest
+        if self.problem.is_some() && self.cache.is_some() {
+            return Err(SimpleError::Custom {
+                message: "--problem keeps its own cache, it cannot be used with --cache".to_owned(),
+            });
+        }
+        if self.pseudonym_map.is_some() && self.public_export.is_none() {
+            return Err(SimpleError::Custom {
+                message: "--pseudonym-map only goes with --public-export".to_owned(),
+            });
+        }
+        // The synced cache and the printout would share stdout
+        if self.prints_board() && matches!(&self.cache, Some(path) if is_std_stream(path)) {
+            return Err(SimpleError::Custom {
+                message: "--cache - saves to stdout, it cannot be used with a printing mode"
+                    .to_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Modes which sync the board, save it and print it to stdout
+    pub fn prints_board(&self) -> bool {
+        self.export.is_some()
+            || self.public_export.is_some()
+            || self.user_report.is_some()
+            || self.summary
+    }
+
+    pub fn parse_from<I: IntoIterator<Item = String>>(args: I) -> SimpleResult<Self> {
+        let mut opts = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => {
+                    opts.config = Some(next_value(&mut args, &arg)?.into());
+                }
+                "--tab" => {
+                    opts.tabs.push(next_value(&mut args, &arg)?.into());
+                }
+                "--history" => {
+                    opts.history_dir = Some(next_value(&mut args, &arg)?.into());
+                }
+                "--offline" => opts.offline = true,
+                "--rebuild" => opts.rebuild = true,
+                "--check-cache" => opts.check_cache = true,
+                "--list-groups" => opts.list_groups = true,
+                "--problems-from-api" => opts.problems_from_api = true,
+                "--summary" => opts.summary = true,
+                "-v" | "--verbose" => opts.verbosity += 1,
+                "-vv" => opts.verbosity += 2,
+                "-q" | "--quiet" => opts.verbosity -= 1,
+                "--export" => {
+                    opts.export = Some(ExportFormat::parse(&next_value(&mut args, &arg)?)?);
+                }
+                "--cache" => {
+                    opts.cache = Some(next_value(&mut args, &arg)?.into());
+                }
+                "--problem" => {
+                    let value = next_value(&mut args, &arg)?;
+                    opts.problem = Some(value.parse().map_err(|_| SimpleError::Custom {
+                        message: format!("Invalid problem id: {}", value),
+                    })?);
+                }
+                "--user-report" => {
+                    let value = next_value(&mut args, &arg)?;
+                    opts.user_report = Some(value.parse().map_err(|_| SimpleError::Custom {
+                        message: format!("Invalid user id: {}", value),
+                    })?);
+                }
+                "--report-format" => {
+                    opts.report_format = Some(ReportFormat::parse(&next_value(&mut args, &arg)?)?);
+                }
+                "--public-export" => {
+                    opts.public_export = Some(ReportFormat::parse(&next_value(&mut args, &arg)?)?);
+                }
+                "--pseudonym-map" => {
+                    opts.pseudonym_map = Some(next_value(&mut args, &arg)?.into());
+                }
+                "--dump-submissions" => {
+                    opts.dump_path = Some(next_value(&mut args, &arg)?.into());
+                }
+                _ => {
+                    return Err(SimpleError::Custom {
+                        message: format!("Unknown argument: {}", arg),
+                    });
+                }
+            }
+        }
+        Ok(opts)
+    }
+
+    /// Where the cache is loaded from, `-` meaning stdin
+    pub fn read_cache_path(&self, meta: &Metadata) -> PathBuf {
+        self.cache_path_or(meta, meta.read_cache_path())
+    }
+
+    /// Where the cache is saved to, `-` meaning stdout
+    pub fn write_cache_path(&self, meta: &Metadata) -> PathBuf {
+        self.cache_path_or(meta, meta.write_cache_path())
+    }
+
+    fn cache_path_or(&self, meta: &Metadata, configured: Option<&Path>) -> PathBuf {
+        let window = meta.window().unwrap_or_default();
+        // The boards of a tabbed session would otherwise share one cache
+        let mut group = if self.tabs.is_empty() {
+            String::new()
+        } else {
+            format!(".g{}", meta.get_groups()[0])
+        };
+        // Columns are numbered by the order of the groups
+        if let Some(columns) = meta.problem_columns() {
+            group.push_str(&format!(".ns{}", columns.groups_key()));
+        }
+        match (&self.cache, self.problem, configured) {
+            // `parse` keeps `--cache` away from `--problem`
+            (Some(path), _, _) => path.clone(),
+            // Its watermark only covers one problem, so keep it apart
+            (None, Some(pid), _) => PathBuf::from(format!(
+                "scoreboard{}.p{}{}.cache",
+                group,
+                pid,
+                window_suffix(&window)
+            )),
+            // Changing the window would otherwise keep what the old one let in
+            (None, None, configured) => with_suffix(
+                configured.unwrap_or_else(|| Path::new(CACHE_FILE)),
+                &format!("{}{}", group, window_suffix(&window)),
+            ),
+        }
+    }
+
+    /// The log level after applying `-v`/`-q` over the build default
+    pub fn log_level(&self) -> LevelFilter {
+        const LEVELS: [LevelFilter; 6] = [
+            LevelFilter::Off,
+            LevelFilter::Error,
+            LevelFilter::Warn,
+            LevelFilter::Info,
+            LevelFilter::Debug,
+            LevelFilter::Trace,
+        ];
+        let default = if cfg!(debug_assertions) {
+            LevelFilter::Debug
+        } else {
+            LevelFilter::Info
+        };
+        let base = LEVELS.iter().position(|l| *l == default).unwrap() as i8;
+        let idx = (base + self.verbosity).clamp(0, LEVELS.len() as i8 - 1);
+        LEVELS[idx as usize]
+    }
+}
+
+/// Like `.w1600000000-`, an open end is left empty
+fn window_suffix(window: &TimeWindow) -> String {
+    if window.is_unbounded() {
+        return String::new();
+    }
+    let stamp = |t: Option<DateTime<Local>>| t.map(|t| t.timestamp().to_string());
+    format!(
+        ".w{}-{}",
+        stamp(window.start).unwrap_or_default(),
+        stamp(window.end).unwrap_or_default()
+    )
+}
+
+/// `scoreboard.cache` with `.g1` becomes `scoreboard.g1.cache`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    if suffix.is_empty() || is_std_stream(path) {
+        return path.to_owned();
+    }
+    let mut name = path.file_stem().unwrap_or_default().to_owned();
+    name.push(suffix);
+    if let Some(ext) = path.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    path.with_file_name(name)
+}
+
+fn next_value<I: Iterator<Item = String>>(args: &mut I, flag: &str) -> SimpleResult<String> {
+    args.next().ok_or_else(|| SimpleError::Custom {
+        message: format!("Missing value for {}", flag),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(args: &[&str]) -> SimpleResult<()> {
+        Options::parse_from(args.iter().map(|&arg| arg.to_owned()))?.check()
+    }
+
+    #[test]
+    fn stdout_cache_stays_apart_from_printouts() {
+        let printing: &[&[&str]] = &[
+            &["--export", "csv"],
+            &["--summary"],
+            &["--user-report", "7"],
+            &["--public-export", "json"],
+        ];
+        for mode in printing {
+            let args: Vec<&str> = ["--cache", "-"]
+                .iter()
+                .chain(mode.iter())
+                .copied()
+                .collect();
+            let err = check(&args).unwrap_err().to_string();
+            assert!(err.contains("--cache -"), "{:?}: {}", mode, err);
+            assert!(check(mode).is_ok(), "{:?}", mode);
+        }
+        // Only read from stdin, or with the TUI drawn on the tty
+        assert!(check(&["--cache", "-", "--check-cache"]).is_ok());
+        assert!(check(&["--cache", "-"]).is_ok());
+        assert!(check(&["--cache", "board.cache", "--export", "csv"]).is_ok());
+    }
+}
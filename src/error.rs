@@ -7,6 +7,7 @@ custom_error! {pub SimpleError
     TomlDeserialize { source: toml::de::Error } = "TOML Deserialize Error",
     Json { source: serde_json::error::Error } = "JSON Serialize/Deserialize Error",
     Binary { source: bincode::Error } = "Binary Serialize/Deserialize Error",
+    Unavailable = "The judge appears to be down or under maintenance, try again later",
     Custom { message: String } = "{message}",
 }
 
@@ -17,3 +18,16 @@ impl From<&str> for SimpleError {
         }
     }
 }
+
+impl SimpleError {
+    /// Whether the judge rejected our token
+    pub fn is_unauthorized(&self) -> bool {
+        match self {
+            SimpleError::Request { source } => matches!(
+                source.status(),
+                Some(reqwest::StatusCode::UNAUTHORIZED) | Some(reqwest::StatusCode::FORBIDDEN)
+            ),
+            _ => false,
+        }
+    }
+}
@@ -15,9 +15,65 @@ use term::Terminal;
 pub struct FakeTermString {
     span_string: SpannedString<Style>,
     current_style: Style,
+    /// The start of a multibyte character split across writes
+    pending: Vec<u8>,
 }
 
-impl FakeTermString {}
+impl FakeTermString {
+    /// Render the styled spans with ANSI SGR escape sequences
+    pub fn to_ansi(&self) -> String {
+        let mut out = String::new();
+        for span in self.span_string.spans() {
+            let params = sgr_params(span.attr);
+            if params.is_empty() {
+                out.push_str(span.content);
+            } else {
+                out.push_str(&format!(
+                    "\x1b[{}m{}\x1b[0m",
+                    params.join(";"),
+                    span.content
+                ));
+            }
+        }
+        out
+    }
+}
+
+fn sgr_params(style: &Style) -> Vec<String> {
+    let mut params = Vec::new();
+    let effects = [
+        (Effect::Bold, "1"),
+        (Effect::Italic, "3"),
+        (Effect::Underline, "4"),
+        (Effect::Reverse, "7"),
+        (Effect::Strikethrough, "9"),
+    ];
+    for &(effect, code) in effects.iter() {
+        if style.effects.contains(effect) {
+            params.push(code.to_string());
+        }
+    }
+    // Palette colors depend on the TUI theme, so only direct colors are kept
+    if let Some(color) = style.color {
+        if let ColorType::Color(c) = color.front {
+            params.extend(sgr_color(c, 30));
+        }
+        if let ColorType::Color(c) = color.back {
+            params.extend(sgr_color(c, 40));
+        }
+    }
+    params
+}
+
+fn sgr_color(color: Color, base: u8) -> Option<String> {
+    match color {
+        Color::TerminalDefault => None,
+        Color::Dark(c) => Some((base + c as u8).to_string()),
+        Color::Light(c) => Some((base + 60 + c as u8).to_string()),
+        Color::Rgb(r, g, b) => Some(format!("{};2;{};{};{}", base + 8, r, g, b)),
+        Color::RgbLowRes(r, g, b) => Some(format!("{};5;{}", base + 8, 16 + 36 * r + 6 * g + b)),
+    }
+}
 
 impl AsRef<SpannedString<Style>> for FakeTermString {
     fn as_ref(&self) -> &SpannedString<Style> {
@@ -31,19 +87,31 @@ impl AsMut<SpannedString<Style>> for FakeTermString {
     }
 }
 
-impl Into<SpannedString<Style>> for FakeTermString {
-    fn into(self) -> SpannedString<Style> {
-        self.span_string
+impl From<FakeTermString> for SpannedString<Style> {
+    fn from(s: FakeTermString) -> Self {
+        s.span_string
     }
 }
 
 impl Write for FakeTermString {
     fn write(&mut self, buf: &[u8]) -> ioResult<usize> {
-        let buf_str = match String::from_utf8(buf.into()) {
-            Ok(s) => s,
-            Err(e) => return Err(ioError::new(ErrorKind::InvalidData, e)),
+        let mut bytes = std::mem::take(&mut self.pending);
+        bytes.extend_from_slice(buf);
+        let valid = match std::str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            // Only an unfinished sequence at the end may wait for more bytes
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => {
+                self.pending = bytes;
+                self.pending.truncate(self.pending.len() - buf.len());
+                return Err(ioError::new(ErrorKind::InvalidData, e));
+            }
         };
-        self.span_string.append_styled(buf_str, self.current_style);
+        self.pending = bytes.split_off(valid);
+        let buf_str = String::from_utf8(bytes).expect("checked above");
+        if !buf_str.is_empty() {
+            self.span_string.append_styled(buf_str, self.current_style);
+        }
         Ok(buf.len())
     }
 
@@ -63,11 +131,18 @@ impl FakeTerm {
             inner: FakeTermString {
                 span_string: SpannedString::new(),
                 current_style: Style::none(),
+                pending: Vec::new(),
             },
         }
     }
 }
 
+impl Default for FakeTerm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Terminal for FakeTerm {
     type Output = FakeTermString;
     fn fg(&mut self, color: TermColor) -> TermResult<()> {
@@ -138,16 +213,16 @@ impl Terminal for FakeTerm {
     }
 
     fn supports_attr(&self, attr: TermAttr) -> bool {
-        match attr {
+        matches!(
+            attr,
             TermAttr::Bold
-            | TermAttr::Reverse
-            | TermAttr::Italic(_)
-            | TermAttr::Underline(_)
-            | TermAttr::Standout(_)
-            | TermAttr::ForegroundColor(_)
-            | TermAttr::BackgroundColor(_) => true,
-            _ => false,
-        }
+                | TermAttr::Reverse
+                | TermAttr::Italic(_)
+                | TermAttr::Underline(_)
+                | TermAttr::Standout(_)
+                | TermAttr::ForegroundColor(_)
+                | TermAttr::BackgroundColor(_)
+        )
     }
 
     fn reset(&mut self) -> TermResult<()> {
@@ -198,3 +273,103 @@ impl Write for FakeTerm {
         self.inner.flush()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use term::color;
+
+    /// Each written piece along with its style
+    fn spans(term: FakeTerm) -> Vec<(String, Style)> {
+        let text = term.into_inner();
+        text.as_ref()
+            .spans()
+            .map(|span| (span.content.to_owned(), *span.attr))
+            .collect()
+    }
+
+    fn colored(front: Color, back: ColorType) -> Option<ColorStyle> {
+        Some(ColorStyle::new(ColorType::Color(front), back))
+    }
+
+    #[test]
+    fn styles_follow_fg_bg_attr_and_reset() {
+        let mut term = FakeTerm::new();
+        term.fg(color::RED).unwrap();
+        write!(term, "a").unwrap();
+        term.attr(TermAttr::Bold).unwrap();
+        write!(term, "b").unwrap();
+        term.bg(color::BLUE).unwrap();
+        write!(term, "c").unwrap();
+        // Bold toggles, so a second one turns it off again
+        term.attr(TermAttr::Bold).unwrap();
+        write!(term, "d").unwrap();
+        term.reset().unwrap();
+        write!(term, "e").unwrap();
+        term.fg(196).unwrap();
+        write!(term, "f").unwrap();
+
+        let red = Color::Dark(BaseColor::Red);
+        let primary = ColorStyle::primary().back;
+        let blue = ColorType::Color(Color::Dark(BaseColor::Blue));
+        let spans = spans(term);
+        let styles: Vec<(&str, Option<ColorStyle>, bool)> = spans
+            .iter()
+            .map(|(text, style)| {
+                let bold = style.effects.contains(Effect::Bold);
+                (text.as_str(), style.color, bold)
+            })
+            .collect();
+        assert_eq!(
+            styles,
+            vec![
+                ("a", colored(red, primary), false),
+                ("b", colored(red, primary), true),
+                ("c", colored(red, blue), true),
+                ("d", colored(red, blue), false),
+                ("e", None, false),
+                ("f", colored(Color::RgbLowRes(5, 0, 0), primary), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn reverse_toggles_and_standout_sets() {
+        let mut term = FakeTerm::new();
+        term.attr(TermAttr::Reverse).unwrap();
+        write!(term, "a").unwrap();
+        term.attr(TermAttr::Reverse).unwrap();
+        write!(term, "b").unwrap();
+        term.attr(TermAttr::Standout(true)).unwrap();
+        term.attr(TermAttr::Standout(true)).unwrap();
+        write!(term, "c").unwrap();
+        term.attr(TermAttr::Standout(false)).unwrap();
+        write!(term, "d").unwrap();
+
+        let reversed: Vec<bool> = spans(term)
+            .iter()
+            .map(|(_, style)| style.effects.contains(Effect::Reverse))
+            .collect();
+        assert_eq!(reversed, vec![true, false, true, false]);
+    }
+
+    #[test]
+    fn multibyte_char_split_across_writes() {
+        let mut term = FakeTerm::new();
+        let bytes = "é中".as_bytes();
+        assert_eq!(term.write(&bytes[..1]).unwrap(), 1);
+        assert_eq!(term.write(&bytes[1..3]).unwrap(), 2);
+        assert_eq!(term.write(&bytes[3..]).unwrap(), 2);
+        let text: String = spans(term.clone())
+            .into_iter()
+            .map(|(text, _)| text)
+            .collect();
+        assert_eq!(text, "é中");
+
+        // Bytes which can never become UTF-8 still fail
+        assert_eq!(
+            term.write(&[0xff]).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,42 @@
+//! The scoreboard core, shared by the `FOJ_scoreboard` binary
+//!
+//! Fetch with `FojApi` and `sync`, keep the results in a `Scoreboard`,
+//! then render it with `gen_table` or one of its exporters.
+//! The usual imports are gathered in `prelude`.
+
+extern crate bincode;
+extern crate chrono;
+extern crate chrono_tz;
+#[macro_use]
+extern crate custom_error;
+extern crate cursive;
+extern crate dirs;
+extern crate futures;
+#[macro_use]
+extern crate log;
+#[macro_use]
+extern crate prettytable;
+extern crate reqwest;
+extern crate serde;
+extern crate term;
+extern crate toml;
+
+pub mod api;
+pub mod error;
+pub mod fake_term;
+pub mod meta;
+pub mod prelude;
+pub mod scoreboard;
+pub mod theme;
+
+pub use self::api::{
+    AuthScheme, Checkpoint, FojApi, Group, Paging, Problem, RetryPolicy, Session, Submission,
+    Verdict,
+};
+pub use self::error::{SimpleError, SimpleResult};
+pub use self::fake_term::{FakeTerm, FakeTermString};
+pub use self::meta::{Metadata, TimeWindow};
+pub use self::scoreboard::{
+    sync, CoverageSummary, FetchCount, ProblemOrigin, ProblemStats, ReportFormat, Scoreboard,
+    SyncConfig,
+};
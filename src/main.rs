@@ -1,129 +1,1377 @@
 #![allow(non_snake_case)]
 
 extern crate chrono;
-#[macro_use]
-extern crate custom_error;
+extern crate ctrlc;
 extern crate cursive;
+extern crate foj_scoreboard;
+extern crate futures;
 #[macro_use]
+extern crate log;
 extern crate prettytable;
-extern crate bincode;
-extern crate reqwest;
-extern crate serde;
 extern crate term;
 extern crate tokio;
 extern crate tokio_timer;
-extern crate toml;
-#[macro_use]
-extern crate log;
-extern crate futures;
+extern crate unicode_width;
 
-mod api;
-mod error;
-mod fake_term;
-mod meta;
-mod scoreboard;
-
-use self::error::SimpleResult;
-use self::fake_term::FakeTermString;
-use self::meta::Metadata;
-use self::scoreboard::Scoreboard;
-use cursive::theme::*;
-use cursive::traits::Identifiable;
-use cursive::view::Selector;
-use cursive::views::{DebugView, Dialog, ScrollView, TextView};
-use cursive::Cursive;
-use log::LevelFilter;
+mod cli;
+
+use self::cli::{ExportFormat, Options};
+use chrono::Local;
+use cursive::event::{Event, EventResult, Key};
+use cursive::theme::{BaseColor, Color, Effect};
+use cursive::traits::{Boxable, Identifiable};
+use cursive::utils::markup::StyledString;
+use cursive::view::{Offset, Position, Selector};
+use cursive::views::{
+    Dialog, EditView, IdView, LinearLayout, OnEventView, ScrollView, SelectView, TextView,
+};
+use cursive::{CbSink, Cursive};
+use foj_scoreboard::scoreboard::{
+    self, ReportFormat, Scoreboard, StatusFilter, SyncConfig, TableOptions,
+};
+use foj_scoreboard::{
+    fake_term, AuthScheme, FakeTermString, FojApi, Metadata, Paging, RetryPolicy, SimpleError,
+    SimpleResult,
+};
+use futures::future::Future;
+use prettytable::Table;
+use std::collections::BTreeSet;
 use std::error::Error;
-use std::sync::Arc;
+use std::fs::File;
+use std::io::{BufWriter, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
 use term::Terminal as _;
+use tokio::runtime::{Runtime, TaskExecutor};
 use tokio_timer::clock::Clock;
+use unicode_width::UnicodeWidthStr;
 
-fn sync_get_content(board: Arc<Scoreboard>, meta: &Metadata) -> SimpleResult<FakeTermString> {
-    let mut runtime = tokio::runtime::Builder::new().clock(Clock::new()).build()?;
+fn sync_get_content(
+    runtime: &mut Runtime,
+    board: Arc<Scoreboard>,
+    foj: Arc<FojApi>,
+    meta: &Metadata,
+    opts: &Options,
+) -> SimpleResult<FakeTermString> {
     runtime.block_on(scoreboard::sync(
         board.clone(),
-        meta.get_group(),
-        meta.get_token().to_owned(),
+        foj,
+        SyncConfig::new(meta, opts.problem),
     ))?;
+    save_and_render(&board, meta, opts, Selection::default())
+}
+
+/// Everything after a successful sync: persist the board and draw it
+fn save_and_render(
+    board: &Scoreboard,
+    meta: &Metadata,
+    opts: &Options,
+    sel: Selection,
+) -> SimpleResult<FakeTermString> {
+    let start = Instant::now();
+    save_unless_stdout(board, &opts.write_cache_path(meta))?;
+    if let Some(dir) = &opts.history_dir {
+        let path = board.save_snapshot(dir)?;
+        info!("Snapshot saved to {}", path.display());
+    }
+    debug!("Saved the cache in {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let content = render_content(board, meta, opts, sel)?;
+    debug!("Generated the table in {:?}", start.elapsed());
+    Ok(content)
+}
+
+fn export(
+    board: &Scoreboard,
+    meta: &Metadata,
+    opts: &Options,
+    sel: Selection,
+    format: ExportFormat,
+) -> SimpleResult<String> {
+    // Same rows as on screen, as focused, filtered and searched
+    let table_opts = TableOptions {
+        focus: sel.focus,
+        filter: sel.filter,
+        search: sel.search.as_deref(),
+    };
+    match format {
+        ExportFormat::Ansi => {
+            // The highlighted cell is only meant for the screen
+            let sel = Selection {
+                cursor: None,
+                ..sel.clone()
+            };
+            Ok(render_content(board, meta, opts, sel)?.to_ansi())
+        }
+        ExportFormat::Csv => Ok(board.export_csv(meta, &table_opts)),
+        ExportFormat::Json => board.export_json(meta, &table_opts),
+    }
+}
+
+/// Export into a timestamped file in the working directory
+fn export_to_file(
+    board: &Scoreboard,
+    meta: &Metadata,
+    opts: &Options,
+    sel: Selection,
+    format: ExportFormat,
+) -> SimpleResult<PathBuf> {
+    let path = PathBuf::from(format!(
+        "scoreboard-{}.{}",
+        Local::now().format("%Y%m%d-%H%M%S"),
+        format.extension()
+    ));
+    std::fs::write(&path, export(board, meta, opts, sel, format)?)?;
+    Ok(path)
+}
+
+/// Put a background refresh's outcome on screen and close its dialog
+///
+/// A failed refresh keeps the last table, `manual` ones also open the log.
+fn show_refresh_result(s: &mut Cursive, result: SimpleResult<FakeTermString>, manual: bool) {
+    let status = match result {
+        Ok(content) => {
+            s.call_on(&Selector::Id("table"), |view: &mut TextView| {
+                view.set_content(content);
+            });
+            update_overflow_hint(s);
+            LinkStatus::Online
+        }
+        Err(e) => {
+            error!("{}", e);
+            LinkStatus::Error
+        }
+    };
+    s.call_on(&Selector::Id("status"), |view: &mut TextView| {
+        view.set_content(status_line(status));
+    });
+    // Other layers may have been opened on top while waiting
+    if let Some(pos) = s.screen_mut().find_layer_from_id("refr_dlg") {
+        s.screen_mut().remove_layer(pos);
+    }
+    if status == LinkStatus::Error && manual {
+        s.show_debug_console();
+    }
+}
+
+/// Write every submission the board counts as JSON lines, leaving the cache untouched
+fn dump_submissions(meta: &Metadata, problem: Option<u32>, path: &Path) -> SimpleResult<()> {
+    let mut runtime = tokio::runtime::Builder::new().clock(Clock::new()).build()?;
+    let foj = FojApi::with_auth(
+        meta.get_token().to_owned(),
+        meta.auth_scheme(),
+        meta.base_url(),
+    )?
+    .with_retry(meta.retry_policy())
+    .with_paging(meta.paging());
+    // The judge's own problem ids, rather than the board's columns
+    let config = SyncConfig {
+        columns: None,
+        ..SyncConfig::new(meta, problem)
+    };
+    let submissions = runtime.block_on(scoreboard::fetch_submissions(&foj, &config))?;
+
+    let mut f = BufWriter::new(File::create(path)?);
+    for sub in &submissions {
+        serde_json::to_writer(&mut f, sub)?;
+        writeln!(f)?;
+    }
+    f.flush()?;
+    Ok(())
+}
+
+fn list_groups(meta: &Metadata) -> SimpleResult<()> {
+    if meta.get_token().is_empty() {
+        return Err(SimpleError::Custom {
+            message: "User token not set! Fill in user_token in meta.toml.".to_owned(),
+        });
+    }
+    let mut runtime = tokio::runtime::Builder::new().clock(Clock::new()).build()?;
+    let foj = FojApi::with_auth(
+        meta.get_token().to_owned(),
+        meta.auth_scheme(),
+        meta.base_url(),
+    )?
+    .with_retry(meta.retry_policy())
+    .with_paging(meta.paging());
+    for group in runtime.block_on(foj.get_groups())? {
+        println!("{}\t{}", group.id, group.name);
+    }
+    Ok(())
+}
 
-    board.save_cache("scoreboard.cache")?;
+/// The problems everyone or nobody solved, one line each
+fn summary_text(board: &Scoreboard, meta: &Metadata) -> String {
+    let summary = board.coverage_summary(meta);
+    let list = |set: &BTreeSet<u32>| {
+        if set.is_empty() {
+            "-".to_owned()
+        } else {
+            let ids: Vec<String> = set.iter().map(|&pid| meta.problem_label(pid)).collect();
+            ids.join(", ")
+        }
+    };
+    format!(
+        "Over {} users\nSolved by everyone: {}\nSolved by nobody: {}\n",
+        summary.users,
+        list(&summary.solved_by_all),
+        list(&summary.solved_by_none)
+    )
+}
+
+/// Print every broken invariant of the cache, failing if there is any
+fn check_cache(path: &Path) -> SimpleResult<()> {
+    let board = Scoreboard::load_cache(path)?;
+    let problems = board.check_integrity();
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    if !problems.is_empty() {
+        return Err(SimpleError::Custom {
+            message: format!("{} problems found in {}", problems.len(), path.display()),
+        });
+    }
+    println!("{} looks fine", path.display());
+    Ok(())
+}
+
+/// Lock `m`, the data stays usable even if a callback panicked holding it
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Save the cache, unless it goes to stdout, which takes a single snapshot
+/// once the TUI exits, see `save_held_caches`
+fn save_unless_stdout(board: &Scoreboard, path: &Path) -> SimpleResult<()> {
+    if scoreboard::is_std_stream(path) {
+        return Ok(());
+    }
+    board.save_cache(path)
+}
+
+/// Write the one snapshot of each board whose cache goes to stdout
+fn save_held_caches(to_save: &SaveList) -> SimpleResult<()> {
+    for (board, path) in lock(to_save).iter() {
+        if scoreboard::is_std_stream(path) {
+            board.save_cache(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Try to keep whatever data we have when being interrupted
+fn save_on_interrupt(board: &Scoreboard, path: &Path) {
+    match board.save_cache(path) {
+        Ok(()) => info!("Interrupted, cache saved"),
+        Err(e) => error!("Interrupted, failed to save the cache: {}", e),
+    }
+}
+
+/// Save the cache every `interval` until the process exits
+///
+/// Refreshes save on their own; this covers what changed in between. A
+/// save waits for a sync counting submissions, see `Scoreboard::save_cache`.
+fn spawn_autosave(board: Arc<Scoreboard>, path: PathBuf, interval: Duration) {
+    if scoreboard::is_std_stream(&path) {
+        warn!("Autosave is off while the cache goes to stdout");
+        return;
+    }
+    std::thread::spawn(move || loop {
+        std::thread::sleep(interval);
+        match board.save_cache(&path) {
+            Ok(()) => debug!("Autosaved the cache"),
+            Err(e) => error!("Failed to autosave the cache: {}", e),
+        }
+    });
+}
+
+/// What the user picked on the grid
+#[derive(Clone, Debug, Default)]
+struct Selection {
+    /// The problem picked with 's' to sort the rows by
+    focus: Option<u32>,
+    /// The highlighted `(row, col)` of the table, see `move_cursor`
+    cursor: Option<(usize, usize)>,
+    /// The rows kept by the 'v' key
+    filter: Option<(u32, StatusFilter)>,
+    /// Part of the names kept by the '/' prompt
+    search: Option<String>,
+}
+
+/// The table shown outside of the feed, before any cell is highlighted
+fn grid_table(board: &Scoreboard, meta: &Metadata, opts: &Options, sel: &Selection) -> Table {
+    match opts.problem {
+        Some(pid) => board.gen_problem_table(pid, meta),
+        None => board.gen_table(
+            meta,
+            &TableOptions {
+                focus: sel.focus,
+                filter: sel.filter,
+                search: sel.search.as_deref(),
+            },
+        ),
+    }
+}
+
+/// The user on the highlighted row, none while no row is
+fn selected_user(
+    board: &Scoreboard,
+    meta: &Metadata,
+    opts: &Options,
+    sel: &Selection,
+) -> Option<u32> {
+    let (row, _) = sel.cursor?;
+    match opts.problem {
+        Some(pid) => board.problem_user_at_row(pid, meta, row),
+        None => board.user_at_row(
+            meta,
+            &TableOptions {
+                focus: sel.focus,
+                filter: sel.filter,
+                search: sel.search.as_deref(),
+            },
+            row,
+        ),
+    }
+}
+
+fn render_content(
+    board: &Scoreboard,
+    meta: &Metadata,
+    opts: &Options,
+    sel: Selection,
+) -> SimpleResult<FakeTermString> {
     let mut fterm = fake_term::FakeTerm::new();
 
-    board.gen_table(meta.problems()).print_term(&mut fterm)?;
+    if board.is_empty() {
+        let groups: Vec<String> = meta.get_groups().iter().map(|g| g.to_string()).collect();
+        writeln!(fterm, "No submissions yet for group {}", groups.join(", "))?;
+        writeln!(fterm, "Press 'r' to refresh.")?;
+        return Ok(fterm.into_inner());
+    }
+    let mut table = grid_table(board, meta, opts, &sel);
+    if let Some(cursor) = sel.cursor {
+        scoreboard::highlight_cell(&mut table, cursor);
+    }
+    table.print_term(&mut fterm)?;
     Ok(fterm.into_inner())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut palette = Palette::default();
-    palette[PaletteColor::Background] = Color::Dark(BaseColor::Black);
-    palette[PaletteColor::Primary] = Color::Dark(BaseColor::White);
-    palette[PaletteColor::View] = Color::Dark(BaseColor::Black);
-    palette[PaletteColor::Shadow] = Color::Light(BaseColor::Black);
-    let mut theme = Theme::default();
-    theme.shadow = false;
-    theme.palette = palette;
+/// Move the highlighted cell by `step` rows and columns, then keep it on screen
+///
+/// The first key press only shows the cursor, at the top left corner.
+fn move_cursor(
+    s: &mut Cursive,
+    board: &Scoreboard,
+    meta: &Metadata,
+    opts: &Options,
+    selection: &Mutex<Selection>,
+    (rows, cols): (isize, isize),
+) {
+    if board.is_empty() {
+        return;
+    }
+    let mut sel = lock(selection);
+    let target = match sel.cursor {
+        Some((row, col)) => (
+            (row as isize + rows).max(0) as usize,
+            (col as isize + cols).max(0) as usize,
+        ),
+        None => (0, 0),
+    };
+    let mut table = grid_table(board, meta, opts, &sel);
+    sel.cursor = Some(scoreboard::highlight_cell(&mut table, target));
+    drop(sel);
 
-    let mut csiv = Cursive::default();
-    csiv.set_theme(theme);
-    cursive::logger::init();
-    if cfg!(debug_assertions) {
-        log::set_max_level(LevelFilter::Debug);
+    let mut fterm = fake_term::FakeTerm::new();
+    if let Err(e) = table.print_term(&mut fterm) {
+        error!("{}", e);
+        return;
+    }
+    let content = fterm.into_inner();
+    let area = cursor_area(content.as_ref());
+    s.call_on(&Selector::Id("table"), |view: &mut TextView| {
+        view.set_content(content);
+    });
+    if let Some((line, x, width)) = area {
+        s.call_on(
+            &Selector::Id("scroll"),
+            |view: &mut ScrollView<IdView<TextView>>| {
+                let viewport = view.content_viewport();
+                let mut offset = viewport.top_left();
+                offset.y = scroll_into(offset.y, viewport.height(), line, 1);
+                offset.x = scroll_into(offset.x, viewport.width(), x, width);
+                view.set_offset(offset);
+            },
+        );
+    }
+}
+
+/// The line, column and width of the highlighted cell in a rendered table
+///
+/// Its padding is printed as spans of its own, those add to the width.
+fn cursor_area(content: &StyledString) -> Option<(usize, usize, usize)> {
+    let (mut line, mut x) = (0, 0);
+    let mut spans = content.spans();
+    while let Some(span) = spans.next() {
+        if span.attr.effects.contains(Effect::Reverse) {
+            let width = spans
+                .take_while(|span| span.attr.effects.contains(Effect::Reverse))
+                .take_while(|span| !span.content.contains('\n'))
+                .map(|span| span.width)
+                .sum::<usize>();
+            return Some((line, x, span.width + width));
+        }
+        for (i, part) in span.content.split('\n').enumerate() {
+            if i > 0 {
+                line += 1;
+                x = 0;
+            }
+            x += part.width();
+        }
+    }
+    None
+}
+
+/// The smallest scroll from `offset` showing `[start, start + len)`
+fn scroll_into(offset: usize, visible: usize, start: usize, len: usize) -> usize {
+    if start < offset {
+        start
+    } else if start + len > offset + visible {
+        (start + len).saturating_sub(visible)
     } else {
-        log::set_max_level(LevelFilter::Info);
+        offset
     }
-    csiv.add_layer(DebugView::new());
+}
 
-    let meta = Metadata::load()?;
-    if meta.get_token().is_empty() {
-        return Err("User token not set!".into());
+fn render_feed(board: &Scoreboard, meta: &Metadata) -> SimpleResult<FakeTermString> {
+    let mut fterm = fake_term::FakeTerm::new();
+    let lines = board.feed_lines(meta);
+    if lines.is_empty() {
+        writeln!(fterm, "No activity yet.")?;
     }
+    for line in lines {
+        writeln!(fterm, "{}", line)?;
+    }
+    Ok(fterm.into_inner())
+}
 
-    let cache_path = std::path::PathBuf::from("scoreboard.cache");
-    let board = if cache_path.exists() {
-        Scoreboard::load_cache(cache_path)?
+fn render_compare(
+    board: &Scoreboard,
+    meta: &Metadata,
+    a: &str,
+    b: &str,
+) -> SimpleResult<FakeTermString> {
+    let mut fterm = fake_term::FakeTerm::new();
+    let table = board.gen_compare_table(board.find_user(a)?, board.find_user(b)?, meta);
+    table.print_term(&mut fterm)?;
+    Ok(fterm.into_inner())
+}
+
+/// The outcome of the last refresh, shown at the bottom of the screen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LinkStatus {
+    Online,
+    Offline,
+    Error,
+}
+
+fn status_line(status: LinkStatus) -> StyledString {
+    let (text, color) = match status {
+        LinkStatus::Online => ("online", BaseColor::Green),
+        LinkStatus::Offline => ("offline", BaseColor::Yellow),
+        LinkStatus::Error => (
+            "error: last refresh failed, press 'D' for details",
+            BaseColor::Red,
+        ),
+    };
+    StyledString::styled(format!("[{}]", text), Color::Dark(color))
+}
+
+/// Whether the table of each tab wraps, kept as the Cursive user data
+struct ViewState {
+    wrap: Vec<bool>,
+}
+
+/// Point out a clipped table, which could pass for missing data
+fn update_overflow_hint(s: &mut Cursive) {
+    let index = s.active_screen();
+    let wrap = matches!(s.user_data::<ViewState>(), Some(state) if state.wrap[index]);
+    let screen = s.screen_size();
+    let (width, height) = s
+        .call_on(&Selector::Id("table"), |view: &mut TextView| {
+            let content = view.get_content();
+            let lines = content.source().lines();
+            let width = lines.clone().map(UnicodeWidthStr::width).max();
+            (width.unwrap_or(0), lines.count())
+        })
+        .unwrap_or((0, 0));
+    let mut hints = Vec::new();
+    if !wrap && width > screen.x {
+        hints.push("→ more");
+    }
+    // The status line takes the last row
+    if height + 1 > screen.y {
+        hints.push("↓ more");
+    }
+    let text = if hints.is_empty() {
+        String::new()
     } else {
-        Scoreboard::new()
+        format!(" {}, scroll with the arrow keys", hints.join(" "))
     };
+    s.call_on(&Selector::Id("hint"), |view: &mut TextView| {
+        view.set_content(text);
+    });
+}
+
+/// Wrapping only takes effect once the view stops scrolling horizontally
+fn set_wrap(s: &mut Cursive, wrap: bool) {
+    s.call_on(&Selector::Id("table"), |view: &mut TextView| {
+        view.set_content_wrap(wrap);
+    });
+    s.call_on(
+        &Selector::Id("scroll"),
+        |view: &mut ScrollView<IdView<TextView>>| {
+            view.set_scroll_x(!wrap);
+        },
+    );
+    let index = s.active_screen();
+    if let Some(state) = s.user_data::<ViewState>() {
+        state.wrap[index] = wrap;
+    }
+    update_overflow_hint(s);
+}
+
+/// One board of the session, shown on its own Cursive screen
+struct BoardTab {
+    board: Arc<Scoreboard>,
+    meta: Metadata,
+    foj: Arc<FojApi>,
+    /// The problem picked with 's' and the cell picked with the arrows,
+    /// kept across refreshes
+    selection: Arc<Mutex<Selection>>,
+    show_feed: Arc<AtomicBool>,
+    refreshing: Arc<AtomicBool>,
+    /// Refreshes in a row which changed nothing or failed, see
+    /// `Metadata::refresh_delay`
+    quiet: Arc<AtomicU32>,
+}
+
+/// Tabs saving to the same cache would overwrite each other
+fn check_tab_caches(metas: &[Metadata], opts: &Options) -> SimpleResult<()> {
+    let mut seen = BTreeSet::new();
+    for meta in metas {
+        let path = opts.write_cache_path(meta);
+        if !seen.insert(path.clone()) {
+            return Err(SimpleError::Custom {
+                message: format!(
+                    "Two tabs save to {}, set write_cache_path in one of them",
+                    path.display()
+                ),
+            });
+        }
+    }
+    Ok(())
+}
 
+/// The token, how it is sent, the base URL and retries of a client
+type ClientKey = (String, AuthScheme, String, RetryPolicy, Paging);
+
+/// Every board the Ctrl-C handler saves, with the cache it goes to
+type SaveList = Mutex<Vec<(Arc<Scoreboard>, PathBuf)>>;
+
+/// Load the board of `meta` and bring it up to date
+///
+/// Boards logging in to the same judge with the same token share one client.
+fn open_tab(
+    runtime: &mut Runtime,
+    clients: &mut Vec<(ClientKey, Arc<FojApi>)>,
+    meta: Metadata,
+    opts: &Options,
+    to_save: &SaveList,
+) -> SimpleResult<(BoardTab, FakeTermString)> {
+    let cache_path = opts.read_cache_path(&meta);
+    let cache_exists = scoreboard::is_std_stream(&cache_path) || cache_path.exists();
+    let board = if opts.offline {
+        if !cache_exists {
+            return Err(SimpleError::Custom {
+                message: format!(
+                    "No cache to display in offline mode! Looked for {}",
+                    cache_path.display()
+                ),
+            });
+        }
+        Scoreboard::load_cache(&cache_path)?
+    } else if opts.rebuild {
+        info!("Rebuilding the board, every submission is fetched again");
+        Scoreboard::new()
+    } else if cache_exists {
+        Scoreboard::load_cache(&cache_path).unwrap_or_else(|e| {
+            warn!("Failed to load the cache, rebuilding it: {}", e);
+            Scoreboard::new()
+        })
+    } else {
+        Scoreboard::new()
+    };
     let board = Arc::new(board);
-    let content = sync_get_content(board.clone(), &meta)?;
 
-    csiv.pop_layer();
-    let view = TextView::new(content).no_wrap().with_id("table");
-    csiv.add_fullscreen_layer(ScrollView::new(view).scroll_x(true).show_scrollbars(false));
+    let key = (
+        meta.get_token().to_owned(),
+        meta.auth_scheme(),
+        meta.base_url().to_owned(),
+        meta.retry_policy(),
+        meta.paging(),
+    );
+    let foj = match clients.iter().find(|(k, _)| *k == key) {
+        Some((_, foj)) => foj.clone(),
+        None => {
+            let foj = Arc::new(
+                FojApi::with_auth(key.0.clone(), key.1, &key.2)?
+                    .with_retry(key.3)
+                    .with_paging(key.4),
+            );
+            clients.push((key, foj.clone()));
+            foj
+        }
+    };
+    lock(to_save).push((board.clone(), opts.write_cache_path(&meta)));
+    let content = if opts.offline {
+        render_content(&board, &meta, opts, Selection::default())?
+    } else {
+        sync_get_content(runtime, board.clone(), foj.clone(), &meta, opts)?
+    };
+    let tab = BoardTab {
+        board,
+        meta,
+        foj,
+        selection: Arc::default(),
+        show_feed: Arc::new(AtomicBool::new(false)),
+        refreshing: Arc::new(AtomicBool::new(false)),
+        quiet: Arc::default(),
+    };
+    Ok((tab, content))
+}
 
-    csiv.add_global_callback('q', |s| s.quit());
-    csiv.add_global_callback('D', |s| s.toggle_debug_console());
-    csiv.add_global_callback('r', move |s| {
-        let board = board.clone();
+/// Names of all tabs with the shown one highlighted, empty with a single tab
+fn tab_bar(labels: &[String], active: usize) -> StyledString {
+    let mut bar = StyledString::new();
+    if labels.len() < 2 {
+        return bar;
+    }
+    for (i, label) in labels.iter().enumerate() {
+        let text = format!(" {} {} ", i + 1, label);
+        if i == active {
+            bar.append_styled(text, Effect::Reverse);
+        } else {
+            bar.append_plain(text);
+        }
+    }
+    bar.append_plain(" ");
+    bar
+}
+
+/// Fill the active screen with the table of `tab` and its bottom bar
+fn add_tab_layer(
+    s: &mut Cursive,
+    tab: &BoardTab,
+    opts: &Options,
+    content: FakeTermString,
+    status: LinkStatus,
+    bar: StyledString,
+) {
+    let wrap = tab.meta.wrap_table();
+    let mut view = TextView::new(content);
+    view.set_content_wrap(wrap);
+    let view = view.with_id("table");
+    let mut scroll = OnEventView::new(
+        ScrollView::new(view)
+            .scroll_x(!wrap)
+            .show_scrollbars(false)
+            .with_id("scroll"),
+    );
+    if tab.meta.cell_navigation() {
+        let keys = [
+            (Key::Up, (-1, 0)),
+            (Key::Down, (1, 0)),
+            (Key::Left, (0, -1)),
+            (Key::Right, (0, 1)),
+        ];
+        for &(key, step) in keys.iter() {
+            let board = tab.board.clone();
+            let meta = tab.meta.clone();
+            let opts = opts.clone();
+            let selection = tab.selection.clone();
+            let show_feed = tab.show_feed.clone();
+            // Taken before the scroll view sees it, which scrolls the feed as usual
+            scroll.set_on_pre_event_inner(key, move |_, _| {
+                if show_feed.load(Ordering::SeqCst) {
+                    return None;
+                }
+                let board = board.clone();
+                let meta = meta.clone();
+                let opts = opts.clone();
+                let selection = selection.clone();
+                Some(EventResult::with_cb(move |s| {
+                    move_cursor(s, &board, &meta, &opts, &selection, step)
+                }))
+            });
+        }
+    }
+    s.add_fullscreen_layer(
+        LinearLayout::vertical().child(scroll.full_screen()).child(
+            LinearLayout::horizontal()
+                .child(TextView::new(bar))
+                .child(TextView::new(status_line(status)).with_id("status"))
+                .child(TextView::new("").with_id("hint")),
+        ),
+    );
+}
+
+/// Sync the board of tab `index` in the background, then redraw it
+///
+/// Only `manual` refreshes, those from the 'r' key, put up a dialog.
+fn start_refresh(
+    s: &mut Cursive,
+    tabs: &[BoardTab],
+    index: usize,
+    executor: &TaskExecutor,
+    opts: &Options,
+    manual: bool,
+) {
+    let tab = &tabs[index];
+    // Skip it while the previous refresh is still running
+    if tab.refreshing.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    if manual {
         s.add_layer(
             Dialog::text("Refreshing data. Please wait...")
                 .title("Refreshing")
                 .with_id("refr_dlg"),
         );
         s.focus(&Selector::Id("refr_dlg")).unwrap();
-        s.refresh();
-        if s.call_on(
-            &Selector::Id("table"),
-            |table_view: &mut TextView| match sync_get_content(board, &meta) {
+    }
+
+    let board = tab.board.clone();
+    let meta = tab.meta.clone();
+    let opts = opts.clone();
+    let refreshing = tab.refreshing.clone();
+    let show_feed = tab.show_feed.clone();
+    let quiet = tab.quiet.clone();
+    let revision = board.revision();
+    let sel = lock(&tab.selection).clone();
+    let cb_sink = s.cb_sink().clone();
+    let task = scoreboard::sync(
+        board.clone(),
+        tab.foj.clone(),
+        SyncConfig::new(&meta, opts.problem),
+    )
+    .then(move |res| {
+        if res.is_ok() && board.revision() != revision {
+            quiet.store(0, Ordering::SeqCst);
+        } else {
+            quiet.fetch_add(1, Ordering::SeqCst);
+        }
+        let mut result = res.and_then(|_| save_and_render(&board, &meta, &opts, sel));
+        if show_feed.load(Ordering::SeqCst) {
+            result = result.and_then(|_| render_feed(&board, &meta));
+        }
+        let _ = cb_sink.send(Box::new(move |s: &mut Cursive| {
+            // The user may have moved to another tab meanwhile
+            let current = s.active_screen();
+            s.set_screen(index);
+            show_refresh_result(s, result, manual);
+            s.set_screen(current);
+            refreshing.store(false, Ordering::SeqCst);
+        }));
+        Ok(())
+    });
+    executor.spawn(task);
+}
+
+/// Run `refresh` on the UI thread after each `delay` until the TUI is gone
+///
+/// The delay is asked again while waiting, as the refresh before may still
+/// be running when the wait starts.
+fn spawn_auto_refresh<D, F>(cb_sink: CbSink, delay: D, refresh: F)
+where
+    D: Fn() -> Duration + Send + 'static,
+    F: Fn(&mut Cursive) + Send + Sync + 'static,
+{
+    let refresh = Arc::new(refresh);
+    std::thread::spawn(move || loop {
+        let mut waited = Duration::from_secs(0);
+        while waited < delay() {
+            let step = (delay() - waited).min(Duration::from_secs(1));
+            std::thread::sleep(step);
+            waited += step;
+        }
+        let refresh = refresh.clone();
+        if cb_sink.send(Box::new(move |s| refresh(s))).is_err() {
+            break;
+        }
+    });
+}
+
+/// Keep the rows of `tab` whose name contains `query`, every row without one
+fn set_search(s: &mut Cursive, tab: &BoardTab, opts: &Options, query: Option<String>) {
+    let sel = {
+        let mut sel = lock(&tab.selection);
+        sel.search = query
+            .map(|query| query.trim().to_owned())
+            .filter(|query| !query.is_empty());
+        sel.clone()
+    };
+    if tab.show_feed.load(Ordering::SeqCst) {
+        return;
+    }
+    match render_content(&tab.board, &tab.meta, opts, sel) {
+        Ok(content) => {
+            s.call_on(&Selector::Id("table"), |view: &mut TextView| {
+                view.set_content(content);
+            });
+            update_overflow_hint(s);
+        }
+        Err(e) => error!("{}", e),
+    }
+}
+
+fn switch_tab(s: &mut Cursive, index: usize) {
+    s.set_screen(index);
+    update_overflow_hint(s);
+}
+
+/// Edit the note of one user, saved to the cache right away
+fn show_note_editor(
+    s: &mut Cursive,
+    board: Arc<Scoreboard>,
+    meta: Metadata,
+    opts: Options,
+    show_feed: Arc<AtomicBool>,
+    selection: Arc<Mutex<Selection>>,
+    uid: u32,
+) {
+    let editor = EditView::new()
+        .content(board.note(uid).unwrap_or_default())
+        .with_id("note_text")
+        .fixed_width(48);
+    s.add_layer(
+        Dialog::around(editor)
+            .title(format!(
+                "Notes on {}",
+                meta.name_display().show(uid, &board.user_name(uid))
+            ))
+            .button("Save", move |s| {
+                let note = s
+                    .call_on(&Selector::Id("note_text"), |view: &mut EditView| {
+                        view.get_content().to_string()
+                    })
+                    .unwrap_or_default();
+                s.pop_layer();
+                let result = board
+                    .set_note(uid, &note)
+                    .and_then(|_| save_unless_stdout(&board, &opts.write_cache_path(&meta)));
+                if let Err(e) = result {
+                    error!("Failed to save the note: {}", e);
+                    s.add_layer(Dialog::info(format!("Failed to save the note: {}", e)));
+                    return;
+                }
+                if show_feed.load(Ordering::SeqCst) {
+                    return;
+                }
+                match render_content(&board, &meta, &opts, lock(&selection).clone()) {
+                    Ok(content) => {
+                        s.call_on(&Selector::Id("table"), |view: &mut TextView| {
+                            view.set_content(content);
+                        });
+                        update_overflow_hint(s);
+                    }
+                    Err(e) => error!("{}", e),
+                }
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let opts = Options::parse()?;
+    if opts.list_groups {
+        list_groups(&Metadata::read(opts.config.as_deref())?)?;
+        return Ok(());
+    }
+    let mut meta = Metadata::load(opts.config.as_deref())?;
+    if opts.problems_from_api {
+        meta.set_problems_from_api(true);
+    }
+    if opts.check_cache {
+        check_cache(&opts.read_cache_path(&meta))?;
+        return Ok(());
+    }
+    // Like `--cache -`, a meta.toml cache path may name stdout
+    if opts.prints_board() && scoreboard::is_std_stream(&opts.write_cache_path(&meta)) {
+        return Err("The cache is saved to stdout, it cannot be used with a printing mode".into());
+    }
+    if meta.get_token().is_empty() && !opts.offline {
+        return Err("User token not set! Fill in user_token in meta.toml.".into());
+    }
+
+    if let Some(path) = &opts.dump_path {
+        dump_submissions(&meta, opts.problem, path)?;
+        return Ok(());
+    }
+
+    cursive::logger::init();
+    log::set_max_level(opts.log_level());
+
+    // Every board the Ctrl-C handler saves, added as they are opened. Those
+    // saved to stdout are written once, when the TUI exits or on Ctrl-C.
+    let to_save: Arc<SaveList> = Arc::default();
+    if !opts.offline {
+        let to_save = to_save.clone();
+        ctrlc::set_handler(move || {
+            for (board, path) in lock(&to_save).iter() {
+                save_on_interrupt(board, path);
+            }
+            std::process::exit(130);
+        })?;
+    }
+    let mut metas = vec![meta];
+    for path in &opts.tabs {
+        let mut meta = Metadata::load(Some(path))?;
+        if opts.problems_from_api {
+            meta.set_problems_from_api(true);
+        }
+        if meta.get_token().is_empty() && !opts.offline {
+            return Err(format!("User token not set in {}!", path.display()).into());
+        }
+        metas.push(meta);
+    }
+    // Its submissions are fetched by bare problem id, not by column
+    if opts.problem.is_some() && metas.iter().any(|meta| meta.problem_columns().is_some()) {
+        return Err("--problem cannot be used with namespace_problems".into());
+    }
+    check_tab_caches(&metas, &opts)?;
+
+    // Kept for the whole session so the cached login is reused on refresh
+    let mut runtime = tokio::runtime::Builder::new().clock(Clock::new()).build()?;
+    let mut clients = Vec::new();
+    let mut tabs = Vec::with_capacity(metas.len());
+    let mut contents = Vec::with_capacity(metas.len());
+    for meta in metas {
+        let (tab, content) = open_tab(&mut runtime, &mut clients, meta, &opts, &to_save)?;
+        tabs.push(tab);
+        contents.push(content);
+    }
+    let (board, meta) = (&tabs[0].board, &tabs[0].meta);
+
+    if let Some(uid) = opts.user_report {
+        let format = opts.report_format.unwrap_or(ReportFormat::Markdown);
+        print!("{}", board.export_user_report(uid, format, meta)?);
+        return Ok(());
+    }
+    if opts.summary {
+        print!("{}", summary_text(board, meta));
+        return Ok(());
+    }
+    if let Some(format) = opts.public_export {
+        if let Some(path) = &opts.pseudonym_map {
+            std::fs::write(path, board.pseudonym_map(meta)?)?;
+            info!("Pseudonym map saved to {}", path.display());
+        }
+        print!("{}", board.export_public(format, meta)?);
+        return Ok(());
+    }
+    if let Some(format) = opts.export {
+        print!(
+            "{}",
+            export(board, meta, &opts, Selection::default(), format)?
+        );
+        return Ok(());
+    }
+
+    for tab in &tabs {
+        if let Some(interval) = tab.meta.autosave_interval().filter(|_| !opts.offline) {
+            spawn_autosave(
+                tab.board.clone(),
+                opts.write_cache_path(&tab.meta),
+                interval,
+            );
+        }
+    }
+
+    let mut csiv = Cursive::default();
+    csiv.set_theme(tabs[0].meta.theme()?.cursive_theme());
+    let status = if opts.offline {
+        LinkStatus::Offline
+    } else {
+        LinkStatus::Online
+    };
+    let labels: Vec<String> = tabs
+        .iter()
+        .map(|tab| format!("Group {}", tab.meta.get_groups()[0]))
+        .collect();
+    for (i, (tab, content)) in tabs.iter().zip(contents).enumerate() {
+        if i > 0 {
+            csiv.add_active_screen();
+        }
+        add_tab_layer(&mut csiv, tab, &opts, content, status, tab_bar(&labels, i));
+    }
+    csiv.set_user_data(ViewState {
+        wrap: tabs.iter().map(|tab| tab.meta.wrap_table()).collect(),
+    });
+    for i in (0..tabs.len()).rev() {
+        csiv.set_screen(i);
+        update_overflow_hint(&mut csiv);
+    }
+    csiv.add_global_callback(Event::WindowResize, update_overflow_hint);
+    if tabs.len() > 1 {
+        let count = tabs.len();
+        csiv.add_global_callback(Key::Tab, move |s| {
+            let next = (s.active_screen() + 1) % count;
+            switch_tab(s, next);
+        });
+        csiv.add_global_callback(Event::Shift(Key::Tab), move |s| {
+            let prev = (s.active_screen() + count - 1) % count;
+            switch_tab(s, prev);
+        });
+        for i in 0..count.min(9) {
+            let key = std::char::from_digit(i as u32 + 1, 10).unwrap();
+            csiv.add_global_callback(key, move |s| switch_tab(s, i));
+        }
+    }
+    let tabs = Arc::new(tabs);
+
+    csiv.add_global_callback('q', |s| s.quit());
+    {
+        let tabs = tabs.clone();
+        let opts = opts.clone();
+        csiv.add_global_callback('e', move |s| {
+            let tab = &tabs[s.active_screen()];
+            let board = tab.board.clone();
+            let meta = tab.meta.clone();
+            let opts = opts.clone();
+            let sel = lock(&tab.selection).clone();
+            let picker = SelectView::new()
+                .with_all(ExportFormat::ALL.iter().map(|&f| (f.name(), f)))
+                .on_submit(move |s, &format| {
+                    s.pop_layer();
+                    let dialog = match export_to_file(&board, &meta, &opts, sel.clone(), format) {
+                        Ok(path) => Dialog::info(format!("Board written to {}", path.display())),
+                        Err(e) => {
+                            error!("Failed to export the board: {}", e);
+                            Dialog::info(format!("Failed to export the board: {}", e))
+                        }
+                    };
+                    s.add_layer(dialog.title("Export"));
+                });
+            s.add_layer(
+                Dialog::around(picker)
+                    .title("Export as")
+                    .dismiss_button("Cancel"),
+            );
+        });
+    }
+    {
+        let tabs = tabs.clone();
+        csiv.add_global_callback('c', move |s| {
+            let tab = &tabs[s.active_screen()];
+            let board = tab.board.clone();
+            let meta = tab.meta.clone();
+            let prompt = LinearLayout::vertical()
+                .child(TextView::new("First user (name or id)"))
+                .child(EditView::new().with_id("cmp_a").fixed_width(24))
+                .child(TextView::new("Second user (name or id)"))
+                .child(EditView::new().with_id("cmp_b").fixed_width(24));
+            s.add_layer(
+                Dialog::around(prompt)
+                    .title("Compare users")
+                    .button("Compare", move |s| {
+                        let name = |s: &mut Cursive, id| {
+                            s.call_on(&Selector::Id(id), |view: &mut EditView| {
+                                view.get_content().to_string()
+                            })
+                            .unwrap_or_default()
+                        };
+                        let (a, b) = (name(s, "cmp_a"), name(s, "cmp_b"));
+                        s.pop_layer();
+                        let dialog = match render_compare(&board, &meta, &a, &b) {
+                            Ok(content) => Dialog::around(ScrollView::new(TextView::new(content)))
+                                .title("Head to head")
+                                .dismiss_button("Close"),
+                            Err(e) => Dialog::info(e.to_string()).title("Compare users"),
+                        };
+                        s.add_layer(dialog);
+                    })
+                    .dismiss_button("Cancel"),
+            );
+        });
+    }
+    csiv.add_global_callback('D', |s| s.toggle_debug_console());
+    {
+        let tabs = tabs.clone();
+        let opts = opts.clone();
+        csiv.add_global_callback('f', move |s| {
+            let tab = &tabs[s.active_screen()];
+            // Flip between the grid and the activity feed
+            let feed = !tab.show_feed.fetch_xor(true, Ordering::SeqCst);
+            let content = if feed {
+                render_feed(&tab.board, &tab.meta)
+            } else {
+                render_content(&tab.board, &tab.meta, &opts, lock(&tab.selection).clone())
+            };
+            match content {
                 Ok(content) => {
-                    table_view.set_content(content);
-                    Ok(())
+                    s.call_on(&Selector::Id("table"), |view: &mut TextView| {
+                        view.set_content(content);
+                    });
+                    update_overflow_hint(s);
                 }
-                Err(e) => {
-                    error!("{}", e);
-                    Err(e)
+                Err(e) => error!("{}", e),
+            }
+        });
+    }
+    {
+        let tabs = tabs.clone();
+        let opts = opts.clone();
+        csiv.add_global_callback('s', move |s| {
+            let tab = &tabs[s.active_screen()];
+            let board = tab.board.clone();
+            let meta = tab.meta.clone();
+            let opts = opts.clone();
+            let show_feed = tab.show_feed.clone();
+            let selection = tab.selection.clone();
+            let picker = SelectView::new()
+                .item("Default order", None)
+                .with_all(
+                    board
+                        .displayed_problems(&meta)
+                        .into_iter()
+                        .map(|pid| (meta.problem_label(pid), Some(pid))),
+                )
+                .on_submit(move |s, &pick| {
+                    s.pop_layer();
+                    let sel = {
+                        let mut sel = lock(&selection);
+                        sel.focus = pick;
+                        sel.clone()
+                    };
+                    // The feed keeps showing, the order applies once back on the table
+                    if show_feed.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    match render_content(&board, &meta, &opts, sel) {
+                        Ok(content) => {
+                            s.call_on(&Selector::Id("table"), |view: &mut TextView| {
+                                view.set_content(content);
+                            });
+                            update_overflow_hint(s);
+                        }
+                        Err(e) => error!("{}", e),
+                    }
+                });
+            s.add_layer(
+                Dialog::around(ScrollView::new(picker))
+                    .title("Sort by problem")
+                    .dismiss_button("Cancel"),
+            );
+        });
+    }
+    {
+        let tabs = tabs.clone();
+        let opts = opts.clone();
+        csiv.add_global_callback('v', move |s| {
+            let tab = &tabs[s.active_screen()];
+            // Filters on the problem picked with 's'
+            let pid = match lock(&tab.selection).focus {
+                Some(pid) => pid,
+                None => {
+                    s.add_layer(
+                        Dialog::info("Pick a problem with 's' first.").title("Filter rows"),
+                    );
+                    return;
                 }
-            },
-        )
-        .unwrap()
-        .is_err()
-        {
-            s.show_debug_console();
+            };
+            let title = format!("Rows on problem {}", tab.meta.problem_label(pid));
+            let board = tab.board.clone();
+            let meta = tab.meta.clone();
+            let opts = opts.clone();
+            let show_feed = tab.show_feed.clone();
+            let selection = tab.selection.clone();
+            let picker = SelectView::new()
+                .item("Everyone", None)
+                .with_all(
+                    StatusFilter::ALL
+                        .iter()
+                        .map(|&f| (f.name(), Some((pid, f)))),
+                )
+                .on_submit(move |s, &pick| {
+                    s.pop_layer();
+                    let sel = {
+                        let mut sel = lock(&selection);
+                        sel.filter = pick;
+                        sel.clone()
+                    };
+                    if show_feed.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    match render_content(&board, &meta, &opts, sel) {
+                        Ok(content) => {
+                            s.call_on(&Selector::Id("table"), |view: &mut TextView| {
+                                view.set_content(content);
+                            });
+                            update_overflow_hint(s);
+                        }
+                        Err(e) => error!("{}", e),
+                    }
+                });
+            s.add_layer(Dialog::around(picker).title(title).dismiss_button("Cancel"));
+        });
+    }
+    {
+        let tabs = tabs.clone();
+        let opts = opts.clone();
+        csiv.add_global_callback('n', move |s| {
+            let tab = &tabs[s.active_screen()];
+            let board = tab.board.clone();
+            let meta = tab.meta.clone();
+            let opts = opts.clone();
+            let show_feed = tab.show_feed.clone();
+            let selection = tab.selection.clone();
+            // The highlighted row says whose, the prompt is only for when none is
+            let selected = if show_feed.load(Ordering::SeqCst) {
+                None
+            } else {
+                selected_user(&board, &meta, &opts, &lock(&selection))
+            };
+            if let Some(uid) = selected {
+                show_note_editor(s, board, meta, opts, show_feed, selection, uid);
+                return;
+            }
+            let prompt = LinearLayout::vertical()
+                .child(TextView::new("User (name or id)"))
+                .child(EditView::new().with_id("note_user").fixed_width(24));
+            s.add_layer(
+                Dialog::around(prompt)
+                    .title("Notes")
+                    .button("Open", move |s| {
+                        let query = s
+                            .call_on(&Selector::Id("note_user"), |view: &mut EditView| {
+                                view.get_content().to_string()
+                            })
+                            .unwrap_or_default();
+                        s.pop_layer();
+                        match board.find_user(&query) {
+                            Ok(uid) => show_note_editor(
+                                s,
+                                board.clone(),
+                                meta.clone(),
+                                opts.clone(),
+                                show_feed.clone(),
+                                selection.clone(),
+                                uid,
+                            ),
+                            Err(e) => s.add_layer(Dialog::info(e.to_string()).title("Notes")),
+                        }
+                    })
+                    .dismiss_button("Cancel"),
+            );
+        });
+    }
+    {
+        let tabs = tabs.clone();
+        let opts = opts.clone();
+        csiv.add_global_callback('/', move |s| {
+            let index = s.active_screen();
+            let current = lock(&tabs[index].selection).search.clone();
+            let (edit_tabs, edit_opts) = (tabs.clone(), opts.clone());
+            let (esc_tabs, esc_opts) = (tabs.clone(), opts.clone());
+            // The rows are filtered as the name is typed
+            let prompt = EditView::new()
+                .content(current.unwrap_or_default())
+                .on_edit(move |s, text, _| {
+                    set_search(s, &edit_tabs[index], &edit_opts, Some(text.to_owned()))
+                })
+                .on_submit(|s, _| {
+                    s.pop_layer();
+                })
+                .fixed_width(24);
+            let dialog = OnEventView::new(Dialog::around(prompt).title("Search users")).on_event(
+                Key::Esc,
+                move |s| {
+                    s.pop_layer();
+                    set_search(s, &esc_tabs[index], &esc_opts, None);
+                },
+            );
+            // At the bottom, leaving the rows it keeps in sight
+            s.screen_mut().add_layer_at(
+                Position::new(Offset::Center, Offset::Absolute(usize::MAX)),
+                dialog,
+            );
+        });
+    }
+    {
+        let tabs = tabs.clone();
+        let opts = opts.clone();
+        csiv.add_global_callback(Key::Esc, move |s| {
+            let tab = &tabs[s.active_screen()];
+            if lock(&tab.selection).search.is_some() {
+                set_search(s, tab, &opts, None);
+            }
+        });
+    }
+    csiv.add_global_callback('w', |s| {
+        let index = s.active_screen();
+        let wrap = matches!(s.user_data::<ViewState>(), Some(state) if state.wrap[index]);
+        set_wrap(s, !wrap);
+    });
+    // The terminal is in raw mode, so Ctrl-C arrives as a key instead of SIGINT
+    if !opts.offline {
+        let to_save = to_save.clone();
+        csiv.add_global_callback(Event::CtrlChar('c'), move |s| {
+            // Stdout gets its snapshot as the TUI exits
+            for (board, path) in lock(&to_save).iter() {
+                if !scoreboard::is_std_stream(path) {
+                    save_on_interrupt(board, path);
+                }
+            }
+            s.quit();
+        });
+    }
+    if opts.offline {
+        csiv.add_global_callback('r', |s| {
+            s.add_layer(Dialog::info("Refreshing is disabled in offline mode.").title("Offline"));
+        });
+        csiv.set_screen(0);
+        csiv.run();
+        save_held_caches(&to_save)?;
+        return Ok(());
+    }
+    let executor = runtime.executor();
+    for (index, tab) in tabs.iter().enumerate() {
+        if let Some(interval) = tab.meta.refresh_interval() {
+            let tabs = tabs.clone();
+            let executor = executor.clone();
+            let opts = opts.clone();
+            let (meta, quiet) = (tab.meta.clone(), tab.quiet.clone());
+            let delay = move || {
+                meta.refresh_delay(quiet.load(Ordering::SeqCst))
+                    .unwrap_or(interval)
+            };
+            spawn_auto_refresh(csiv.cb_sink().clone(), delay, move |s| {
+                start_refresh(s, &tabs, index, &executor, &opts, false)
+            });
         }
-        s.pop_layer();
+    }
+    csiv.add_global_callback('r', move |s| {
+        let index = s.active_screen();
+        start_refresh(s, &tabs, index, &executor, &opts, true);
     });
+    csiv.set_screen(0);
     csiv.run();
+    save_held_caches(&to_save)?;
 
     Ok(())
 }
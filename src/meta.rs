@@ -1,37 +1,385 @@
-use crate::error::SimpleResult;
+use crate::api::{AuthScheme, Paging, RetryPolicy, Verdict, DEFAULT_BASE_URL};
+use crate::error::{SimpleError, SimpleResult};
+use crate::theme::{color_by_name, BoardTheme, ThemeConfig};
+use chrono::prelude::*;
+use chrono_tz::Tz;
+use prettytable::color::Color;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
 use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+const META_FILE: &str = "meta.toml";
+/// Directory under the user config dir, like `~/.config/FOJ_scoreboard`
+const CONFIG_DIR: &str = "FOJ_scoreboard";
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Metadata {
+    #[serde(default)]
+    mode: BoardMode,
     group_id: u32,
-    user_token: String,
+    extra_groups: Option<Vec<u32>>,
+    #[serde(default)]
+    namespace_problems: bool,
+    contest_id: Option<u32>,
+    user_token: Token,
+    #[serde(default)]
+    auth_scheme: AuthScheme,
+    base_url: Option<String>,
+    retries: Option<u32>,
+    retry_delay_ms: Option<u64>,
+    page_size: Option<usize>,
+    max_submissions: Option<usize>,
     problem_list: Option<Vec<u32>>,
+    include_problems: Option<Vec<u32>>,
+    exclude_problems: Option<Vec<u32>>,
+    exclude_users: Option<Vec<u32>>,
+    #[serde(default)]
+    hide_orphans: bool,
+    #[serde(default)]
+    hide_untitled: bool,
+    #[serde(default)]
+    problems_from_api: bool,
+    #[serde(default)]
+    stripe_rows: bool,
+    #[serde(default)]
+    wrap_table: bool,
+    #[serde(default)]
+    cell_navigation: bool,
+    #[serde(default)]
+    show_last_submit: bool,
+    show_penalty: Option<bool>,
+    show_scores: Option<bool>,
+    recent_minutes: Option<i64>,
+    feed_length: Option<usize>,
+    overlap_seconds: Option<i64>,
+    autosave_seconds: Option<u64>,
+    refresh_seconds: Option<u64>,
+    refresh_max_seconds: Option<u64>,
+    accepted_verdicts: Option<Vec<u32>>,
+    count_compile_errors: Option<bool>,
+    show_footer_header: Option<bool>,
+    timezone: Option<String>,
+    contest_start: Option<String>,
+    freeze_time: Option<String>,
+    window_start: Option<String>,
+    window_end: Option<String>,
+    #[serde(default)]
+    minute_rounding: MinuteRounding,
+    #[serde(default)]
+    penalty_policy: PenaltyPolicy,
+    #[serde(default)]
+    penalty_format: PenaltyFormat,
+    #[serde(default)]
+    score_display: ScoreDisplay,
+    max_scores: Option<BTreeMap<String, i32>>,
+    #[serde(default)]
+    try_count: TryCount,
+    #[serde(default)]
+    show_solve_stats: bool,
+    #[serde(default)]
+    show_solve_ratio: bool,
+    #[serde(default)]
+    show_fetch_count: bool,
+    #[serde(default)]
+    show_source: bool,
+    top_n: Option<usize>,
+    min_ac: Option<usize>,
+    #[serde(default)]
+    global_rank: bool,
+    read_cache_path: Option<PathBuf>,
+    write_cache_path: Option<PathBuf>,
+    #[serde(default)]
+    pin_self: bool,
+    sort_mode: Option<SortMode>,
+    #[serde(default)]
+    name_display: NameDisplay,
+    pseudonym_salt: Option<String>,
+    #[serde(default)]
+    balloon_ac_cells: bool,
+    #[serde(default)]
+    show_verdicts: bool,
+    #[serde(default)]
+    show_pending: bool,
+    balloons: Option<BTreeMap<String, String>>,
+    theme: Option<ThemeConfig>,
+    problem_groups: Option<Vec<ProblemGroup>>,
+    sort_keys: Option<Vec<SortKey>>,
+}
+
+/// The user token, which never shows up in `Debug` output
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Token(String);
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&mask_token(&self.0))
+    }
+}
+
+/// Keep only the last 4 characters, e.g. `tok_****1234`
+pub(crate) fn mask_token(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() <= 8 {
+        return "tok_****".to_owned();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("tok_****{}", tail)
+}
+
+/// How the problems of several groups map to board columns with
+/// `namespace_problems`
+///
+/// A column id packs the problem id with the group's place in `get_groups`,
+/// so the same problem listed by two groups lands in two columns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProblemColumns {
+    gids: Vec<u32>,
+}
+
+impl ProblemColumns {
+    /// The column of `pid` in group `gid`, None for an unknown group or an
+    /// id too large to pack
+    pub fn column(&self, gid: u32, pid: u32) -> Option<u32> {
+        let index = self.gids.iter().position(|&g| g == gid)? as u32;
+        pid.checked_mul(self.gids.len() as u32)?.checked_add(index)
+    }
+
+    /// The groups in column order, like `3-7`
+    pub fn groups_key(&self) -> String {
+        let gids: Vec<String> = self.gids.iter().map(|gid| gid.to_string()).collect();
+        gids.join("-")
+    }
+
+    /// The group and problem id of `column`
+    pub fn origin(&self, column: u32) -> (u32, u32) {
+        let n = self.gids.len() as u32;
+        (self.gids[(column % n) as usize], column / n)
+    }
+}
+
+/// A named set of problems shown together with a subtotal
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProblemGroup {
+    pub name: String,
+    pub problems: Vec<u32>,
 }
 
 impl Metadata {
-    pub fn load() -> SimpleResult<Self> {
-        let config_str = match fs::read_to_string("meta.toml") {
+    /// Load `path`, or the first meta.toml found by `search_path`
+    pub fn load(path: Option<&Path>) -> SimpleResult<Self> {
+        let meta = Self::read(path)?;
+        meta.validate()?;
+        meta.timezone()?;
+        meta.contest_start()?;
+        meta.freeze_time()?;
+        meta.window()?;
+        meta.check_balloons()?;
+        meta.check_max_scores()?;
+        meta.theme()?;
+        Ok(meta)
+    }
+
+    /// Like `load` without checking the settings, enough to reach the judge
+    /// before `group_id` is known
+    pub fn read(path: Option<&Path>) -> SimpleResult<Self> {
+        let path = match path {
+            Some(path) => path.to_owned(),
+            None => Self::search_path(),
+        };
+        let config_str = match fs::read_to_string(&path) {
             Ok(string) => string,
             Err(e) => {
                 if e.kind() == ErrorKind::NotFound {
                     let def_meta = Self::default();
-                    def_meta.save()?;
-                    eprintln!("Meta file not found. A default meta has been generated.");
+                    def_meta.save(&path)?;
+                    eprintln!(
+                        "Meta file not found. A default meta has been generated at {}.",
+                        path.display()
+                    );
                 }
                 return Err(e.into());
             }
         };
-        Ok(toml::from_str(&config_str)?)
+        // The toml error names the key, expected type and position
+        toml::from_str(&config_str).map_err(|e| SimpleError::Custom {
+            message: format!("Invalid meta.toml: {}", e),
+        })
     }
 
-    pub fn get_group(&self) -> u32 {
-        self.group_id
+    /// The user config dir first, then the working directory.
+    /// With neither present, a new meta goes to the config dir.
+    fn search_path() -> PathBuf {
+        let config = dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(META_FILE));
+        let cwd = PathBuf::from(META_FILE);
+        match config {
+            Some(path) if path.exists() => path,
+            _ if cwd.exists() => cwd,
+            Some(path) => path,
+            None => cwd,
+        }
+    }
+
+    /// Catch settings that parse fine but cannot work
+    fn validate(&self) -> SimpleResult<()> {
+        let invalid = |message: &str| {
+            Err(SimpleError::Custom {
+                message: format!("Invalid meta.toml: {}", message),
+            })
+        };
+        if self.group_id == 0 {
+            return invalid("group_id is not set");
+        }
+        if matches!(&self.problem_list, Some(list) if list.is_empty()) {
+            return invalid("problem_list is empty, remove it to show every problem");
+        }
+        if self.top_n == Some(0) {
+            return invalid("top_n must be at least 1");
+        }
+        if self.page_size == Some(0) {
+            return invalid("page_size must be at least 1");
+        }
+        if self.max_submissions == Some(0) {
+            return invalid("max_submissions must be at least 1, remove it to fetch everything");
+        }
+        if self.autosave_seconds == Some(0) {
+            return invalid("autosave_seconds must be at least 1");
+        }
+        if self.refresh_seconds == Some(0) {
+            return invalid("refresh_seconds must be at least 1");
+        }
+        if let Some(max) = self.refresh_max_seconds {
+            match self.refresh_seconds {
+                None => return invalid("refresh_max_seconds needs refresh_seconds"),
+                Some(base) if max < base => {
+                    return invalid("refresh_max_seconds must be at least refresh_seconds")
+                }
+                Some(_) => {}
+            }
+        }
+        // Judges may add their own, but these are never a solve
+        if let Some(&id) = self
+            .accepted_verdicts()
+            .iter()
+            .find(|&&id| matches!(Verdict::from_id(id), Verdict::Pending | Verdict::Judging))
+        {
+            return invalid(&format!("accepted_verdicts has unjudged verdict id {}", id));
+        }
+        if self.namespace_problems {
+            if self.contest_id.is_some() {
+                return invalid("namespace_problems cannot be used with contest_id, contest submissions have no group");
+            }
+            if self.problem_list.is_some()
+                || self.include_problems.is_some()
+                || self.problem_groups.is_some()
+            {
+                return invalid("namespace_problems cannot be used with problem_list, include_problems or problem_groups");
+            }
+        }
+        for group in self.problem_groups() {
+            if group.problems.is_empty() {
+                return invalid(&format!("problem group '{}' has no problems", group.name));
+            }
+        }
+        if matches!(&self.sort_keys, Some(keys) if keys.is_empty()) {
+            return invalid("sort_keys is empty, remove it to use sort_mode");
+        }
+        let by_penalty = self
+            .sort_keys()
+            .iter()
+            .any(|sort| sort.key == SortField::Penalty);
+        if by_penalty && self.contest_start.is_none() {
+            return invalid("ranking by penalty needs contest_start");
+        }
+        Ok(())
+    }
+
+    /// The main group followed by any extra groups merged into the board
+    pub fn get_groups(&self) -> Vec<u32> {
+        let mut groups = vec![self.group_id];
+        for &gid in self.extra_groups.iter().flatten() {
+            if !groups.contains(&gid) {
+                groups.push(gid);
+            }
+        }
+        groups
+    }
+
+    /// Problem columns kept apart per group, None when merged by problem id
+    pub fn problem_columns(&self) -> Option<ProblemColumns> {
+        if self.namespace_problems {
+            Some(ProblemColumns {
+                gids: self.get_groups(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The judge's problem id behind a board column
+    pub fn problem_id(&self, column: u32) -> u32 {
+        match self.problem_columns() {
+            Some(columns) => columns.origin(column).1,
+            None => column,
+        }
+    }
+
+    /// The group a board column was kept apart for, None unless namespaced
+    pub fn problem_group(&self, column: u32) -> Option<u32> {
+        self.problem_columns()
+            .map(|columns| columns.origin(column).0)
+    }
+
+    /// The header of a problem column, like "105", or "G1/105" when namespaced
+    pub fn problem_label(&self, column: u32) -> String {
+        match self.problem_columns() {
+            Some(columns) => {
+                let (gid, pid) = columns.origin(column);
+                format!("G{}/{}", gid, pid)
+            }
+            None => column.to_string(),
+        }
+    }
+
+    /// A contest whose submissions are merged with the groups'
+    pub fn contest(&self) -> Option<u32> {
+        self.contest_id
     }
 
     pub fn get_token(&self) -> &str {
-        &self.user_token
+        &self.user_token.0
+    }
+
+    pub fn auth_scheme(&self) -> AuthScheme {
+        self.auth_scheme
+    }
+
+    /// Where the judge's API lives, for self-hosted instances
+    pub fn base_url(&self) -> &str {
+        self.base_url.as_deref().unwrap_or(DEFAULT_BASE_URL)
+    }
+
+    /// How failed requests are retried, 3 times from 500ms by default
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            retries: self.retries.unwrap_or(default.retries),
+            base_delay: self
+                .retry_delay_ms
+                .map_or(default.base_delay, std::time::Duration::from_millis),
+        }
+    }
+
+    /// How submission lists are paged, 1000 per page with no cap by default
+    pub fn paging(&self) -> Paging {
+        let default = Paging::default();
+        Paging {
+            page_size: self.page_size.unwrap_or(default.page_size),
+            max_submissions: self.max_submissions,
+        }
     }
 
     pub fn problems(&self) -> Option<&[u32]> {
@@ -44,9 +392,850 @@ impl Metadata {
         })
     }
 
-    pub fn save(&self) -> SimpleResult<()> {
+    pub fn include_problems(&self) -> &[u32] {
+        self.include_problems.as_deref().unwrap_or(&[])
+    }
+
+    pub fn exclude_problems(&self) -> &[u32] {
+        self.exclude_problems.as_deref().unwrap_or(&[])
+    }
+
+    /// Users left out before anything is computed, like instructor or bot
+    /// accounts. Unlike `min_ac` or `top_n`, which only hide rows, they count
+    /// towards neither the ranks nor the per-problem stats.
+    pub fn exclude_users(&self) -> &[u32] {
+        self.exclude_users.as_deref().unwrap_or(&[])
+    }
+
+    /// Hide problems whose fetched title is blank, e.g. instructor scratch problems
+    pub fn hide_untitled(&self) -> bool {
+        self.hide_untitled
+    }
+
+    /// Show the group's problem list rather than only submitted problems
+    pub fn problems_from_api(&self) -> bool {
+        self.problems_from_api
+    }
+
+    pub fn set_problems_from_api(&mut self, enabled: bool) {
+        self.problems_from_api = enabled;
+    }
+
+    pub fn hide_orphans(&self) -> bool {
+        self.hide_orphans
+    }
+
+    pub fn stripe_rows(&self) -> bool {
+        self.stripe_rows
+    }
+
+    pub fn timezone(&self) -> SimpleResult<DisplayTz> {
+        match &self.timezone {
+            Some(tz) => DisplayTz::parse(tz),
+            None => Ok(DisplayTz::Local),
+        }
+    }
+
+    pub fn minute_rounding(&self) -> MinuteRounding {
+        self.minute_rounding
+    }
+
+    pub fn penalty_policy(&self) -> PenaltyPolicy {
+        self.penalty_policy
+    }
+
+    pub fn penalty_format(&self) -> PenaltyFormat {
+        self.penalty_format
+    }
+
+    pub fn try_count(&self) -> TryCount {
+        self.try_count
+    }
+
+    /// Accepts RFC 3339 or a local `%Y-%m-%d %H:%M[:%S]`
+    pub fn contest_start(&self) -> SimpleResult<Option<DateTime<Local>>> {
+        parse_time("contest_start", &self.contest_start)
+    }
+
+    /// Verdicts of submissions made from then on are hidden, the cache
+    /// still counts them
+    pub fn freeze_time(&self) -> SimpleResult<Option<DateTime<Local>>> {
+        parse_time("freeze_time", &self.freeze_time)
+    }
+
+    /// Submissions outside of it are left out of the board entirely
+    pub fn window(&self) -> SimpleResult<TimeWindow> {
+        let window = TimeWindow {
+            start: parse_time("window_start", &self.window_start)?,
+            end: parse_time("window_end", &self.window_end)?,
+        };
+        match (window.start, window.end) {
+            (Some(start), Some(end)) if end < start => Err(SimpleError::Custom {
+                message: "window_end is before window_start".to_owned(),
+            }),
+            _ => Ok(window),
+        }
+    }
+
+    pub fn show_solve_stats(&self) -> bool {
+        self.show_solve_stats
+    }
+
+    /// Add a row with the share of users who solved each problem they tried
+    pub fn show_solve_ratio(&self) -> bool {
+        self.show_solve_ratio
+    }
+
+    /// Add the submission count the judge reported to the update row
+    pub fn show_fetch_count(&self) -> bool {
+        self.show_fetch_count
+    }
+
+    /// Add a row with where each problem comes from, like "Codeforces 1520"
+    pub fn show_source(&self) -> bool {
+        self.show_source
+    }
+
+    pub fn top_n(&self) -> Option<usize> {
+        self.top_n
+    }
+
+    /// Users with fewer ACs are left off the board
+    pub fn min_ac(&self) -> Option<usize> {
+        self.min_ac
+    }
+
+    /// Whether users hidden by `min_ac` still count towards the ranks
+    pub fn global_rank(&self) -> bool {
+        self.global_rank
+    }
+
+    pub fn pin_self(&self) -> bool {
+        self.pin_self
+    }
+
+    /// The balloon color assigned to a problem, if any
+    pub fn balloon(&self, pid: u32) -> Option<Color> {
+        self.balloons
+            .as_ref()?
+            .get(&self.problem_id(pid).to_string())
+            .and_then(|name| color_by_name(name))
+    }
+
+    pub fn balloon_ac_cells(&self) -> bool {
+        self.balloon_ac_cells
+    }
+
+    /// Show the last failing verdict, like `TLE`, instead of `WA`
+    pub fn show_verdicts(&self) -> bool {
+        self.show_verdicts
+    }
+
+    /// Mark the cells still waiting on the judge with a `?`
+    pub fn show_pending(&self) -> bool {
+        self.show_pending
+    }
+
+    fn check_balloons(&self) -> SimpleResult<()> {
+        for (pid, name) in self.balloons.iter().flatten() {
+            if pid.parse::<u32>().is_err() {
+                return Err(SimpleError::Custom {
+                    message: format!("Invalid problem id in balloons: {}", pid),
+                });
+            }
+            if color_by_name(name).is_none() {
+                return Err(SimpleError::Custom {
+                    message: format!("Unknown balloon color for problem {}: {}", pid, name),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_max_scores(&self) -> SimpleResult<()> {
+        for (pid, &max) in self.max_scores.iter().flatten() {
+            if pid.parse::<u32>().is_err() {
+                return Err(SimpleError::Custom {
+                    message: format!("Invalid problem id in max_scores: {}", pid),
+                });
+            }
+            if max < 1 {
+                return Err(SimpleError::Custom {
+                    message: format!("The max score of problem {} must be at least 1", pid),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// How partial scores are shown, and ranked with a score sort key
+    pub fn score_display(&self) -> ScoreDisplay {
+        self.score_display
+    }
+
+    /// The full score of `pid`, 100 unless set in `max_scores`
+    pub fn max_score(&self, pid: u32) -> i32 {
+        self.max_scores
+            .as_ref()
+            .and_then(|maxima| maxima.get(&self.problem_id(pid).to_string()))
+            .copied()
+            .unwrap_or(100)
+    }
+
+    /// `score` on `pid` as shown and ranked, see `ScoreDisplay`
+    pub fn scaled_score(&self, pid: u32, score: i32) -> i64 {
+        match self.score_display {
+            ScoreDisplay::Absolute => i64::from(score),
+            ScoreDisplay::Percent => i64::from(score) * 100 / i64::from(self.max_score(pid)),
+        }
+    }
+
+    pub fn mode(&self) -> BoardMode {
+        self.mode
+    }
+
+    /// Ties go to the lower penalty under ICPC rules, see `BoardMode`
+    pub fn sort_mode(&self) -> SortMode {
+        self.sort_mode.unwrap_or(match self.mode {
+            // The penalty is counted from the contest start
+            BoardMode::Icpc if self.contest_start.is_some() => SortMode::Penalty,
+            BoardMode::Icpc => SortMode::FirstToReach,
+            BoardMode::Ioi => SortMode::Score,
+            BoardMode::Practice => SortMode::AcCount,
+        })
+    }
+
+    /// The row order, `sort_mode` spelled out unless `sort_keys` is set
+    pub fn sort_keys(&self) -> Vec<SortKey> {
+        if let Some(keys) = &self.sort_keys {
+            return keys.clone();
+        }
+        let mut keys = vec![SortKey {
+            key: SortField::AcCount,
+            order: SortOrder::Desc,
+        }];
+        match self.sort_mode() {
+            SortMode::AcCount => {}
+            // Full solves only break ties between equal totals
+            SortMode::Score => keys.insert(
+                0,
+                SortKey {
+                    key: SortField::Score,
+                    order: SortOrder::Desc,
+                },
+            ),
+            SortMode::FirstToReach => keys.push(SortKey {
+                key: SortField::LastSolve,
+                order: SortOrder::Asc,
+            }),
+            SortMode::Penalty => keys.push(SortKey {
+                key: SortField::Penalty,
+                order: SortOrder::Asc,
+            }),
+        }
+        keys
+    }
+
+    /// A shared cache may be read from one place while ours is saved elsewhere
+    pub fn read_cache_path(&self) -> Option<&Path> {
+        self.read_cache_path
+            .as_deref()
+            .or(self.write_cache_path.as_deref())
+    }
+
+    pub fn write_cache_path(&self) -> Option<&Path> {
+        self.write_cache_path
+            .as_deref()
+            .or(self.read_cache_path.as_deref())
+    }
+
+    pub fn show_last_submit(&self) -> bool {
+        self.show_last_submit
+    }
+
+    /// Partial scores in the cells and a score column, shown by default when
+    /// the rows are ranked by score
+    pub fn show_scores(&self) -> bool {
+        self.show_scores.unwrap_or_else(|| {
+            self.sort_keys()
+                .iter()
+                .any(|sort| sort.key == SortField::Score)
+        })
+    }
+
+    /// A penalty column, shown by default when the rows are ranked by it
+    pub fn show_penalty(&self) -> bool {
+        self.show_penalty.unwrap_or_else(|| {
+            self.sort_keys()
+                .iter()
+                .any(|sort| sort.key == SortField::Penalty)
+        })
+    }
+
+    /// Submissions newer than this are highlighted as recent activity
+    pub fn recent_minutes(&self) -> i64 {
+        self.recent_minutes.unwrap_or(30)
+    }
+
+    /// How far before the cache time submissions are checked again, covering
+    /// clock differences with the judge. Only used once for caches from
+    /// before the id watermark, and only for cells they don't have yet,
+    /// see `save_submissions`
+    pub fn overlap(&self) -> chrono::Duration {
+        chrono::Duration::seconds(self.overlap_seconds.unwrap_or(5))
+    }
+
+    /// How often the TUI saves the cache between refreshes, never by default
+    pub fn autosave_interval(&self) -> Option<std::time::Duration> {
+        self.autosave_seconds.map(std::time::Duration::from_secs)
+    }
+
+    /// How often the TUI refreshes on its own, only on 'r' by default
+    pub fn refresh_interval(&self) -> Option<std::time::Duration> {
+        self.refresh_seconds.map(std::time::Duration::from_secs)
+    }
+
+    /// The wait before the next auto-refresh after `quiet` of them in a row
+    /// changed nothing or failed, doubling from `refresh_seconds` up to
+    /// `refresh_max_seconds`; without a cap it never grows
+    pub fn refresh_delay(&self, quiet: u32) -> Option<std::time::Duration> {
+        let base = self.refresh_interval()?;
+        let cap = self
+            .refresh_max_seconds
+            .map_or(base, std::time::Duration::from_secs);
+        Some((base * 2u32.saturating_pow(quiet.min(16))).min(cap))
+    }
+
+    /// Verdict ids that count as solving the problem, only AC by default
+    pub fn accepted_verdicts(&self) -> Vec<u32> {
+        self.accepted_verdicts
+            .clone()
+            .unwrap_or_else(|| vec![Verdict::AC.id()])
+    }
+
+    /// Whether a compile error counts as a wrong try, only when practicing by default
+    pub fn count_compile_errors(&self) -> bool {
+        self.count_compile_errors
+            .unwrap_or(self.mode == BoardMode::Practice)
+    }
+
+    /// Verdict ids that count as a wrong try, the judged ones not accepted
+    pub fn rejected_verdicts(&self) -> Vec<u32> {
+        let accepted = self.accepted_verdicts();
+        (Verdict::CE.id()..=Verdict::AC.id())
+            .filter(|id| !accepted.contains(id))
+            .filter(|&id| id != Verdict::CE.id() || self.count_compile_errors())
+            .collect()
+    }
+
+    /// How many events the activity feed shows
+    pub fn feed_length(&self) -> usize {
+        self.feed_length.unwrap_or(50)
+    }
+
+    /// Unless set, the footer problem row only appears on long boards
+    pub fn show_footer_header(&self, rows: usize) -> bool {
+        self.show_footer_header.unwrap_or(rows > 10)
+    }
+
+    pub fn wrap_table(&self) -> bool {
+        self.wrap_table
+    }
+
+    /// Arrow keys move a highlighted cell around instead of scrolling
+    pub fn cell_navigation(&self) -> bool {
+        self.cell_navigation
+    }
+
+    pub fn name_display(&self) -> NameDisplay {
+        self.name_display
+    }
+
+    /// The names used by public exports by user id, salted so they cannot be
+    /// traced back by hashing every user id, so refused without `pseudonym_salt`
+    pub fn pseudonyms(&self) -> SimpleResult<impl Fn(u32) -> String + '_> {
+        let salt = self
+            .pseudonym_salt
+            .as_deref()
+            .filter(|salt| !salt.is_empty())
+            .ok_or_else(|| SimpleError::Custom {
+                message: "Public exports need a pseudonym_salt in meta.toml".to_owned(),
+            })?;
+        Ok(move |uid| format!("User {:08x}", anonymize(salt.as_bytes(), uid)))
+    }
+
+    pub fn theme(&self) -> SimpleResult<BoardTheme> {
+        match &self.theme {
+            Some(theme) => theme.resolve(),
+            None => Ok(BoardTheme::default()),
+        }
+    }
+
+    pub fn problem_groups(&self) -> &[ProblemGroup] {
+        self.problem_groups.as_deref().unwrap_or(&[])
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> SimpleResult<()> {
+        let path = path.as_ref();
         let config_str = toml::to_string_pretty(self)?;
-        fs::write("meta.toml", config_str)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, config_str)?;
         Ok(())
     }
 }
+
+/// Presets for the other settings, any of them set explicitly still wins
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoardMode {
+    /// Every setting at its own default
+    #[default]
+    Practice,
+    /// Ties broken by penalty, or by who got there first without a
+    /// `contest_start`, compile errors are free
+    Icpc,
+    /// Ranked by score, compile errors are free, attempts otherwise don't matter
+    Ioi,
+}
+
+/// How the rows are ordered, spelled out by `Metadata::sort_keys`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortMode {
+    /// Only by AC count, ties keep the user id order
+    #[default]
+    AcCount,
+    /// Ties go to whoever reached that AC count first
+    FirstToReach,
+    /// Ties go to the lower total penalty, which needs `contest_start`
+    Penalty,
+    /// By the sum of the best scores, then by AC count
+    Score,
+}
+
+/// One comparison in the row order, later keys only break ties
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortKey {
+    pub key: SortField,
+    /// Ascending unless set
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+/// What a `SortKey` compares users by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    AcCount,
+    /// When the user got their latest AC, that is reached their AC count
+    LastSolve,
+    LastSubmit,
+    /// Minutes to each AC plus 20 per wrong try before it, as in ICPC
+    Penalty,
+    /// The sum of the best score on each problem, for judges which give one
+    Score,
+    Name,
+    UserId,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// How a solve time is turned into whole minutes since the contest start
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MinuteRounding {
+    /// As in ICPC, 1:59 counts as 1 minute
+    #[default]
+    Floor,
+    Round,
+    Ceil,
+}
+
+impl MinuteRounding {
+    pub fn minutes(self, elapsed: chrono::Duration) -> i64 {
+        let secs = elapsed.num_seconds();
+        match self {
+            MinuteRounding::Floor => secs.div_euclid(60),
+            MinuteRounding::Round => (secs + 30).div_euclid(60),
+            MinuteRounding::Ceil => -(-secs).div_euclid(60),
+        }
+    }
+}
+
+/// Which wrong tries add to the penalty
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PenaltyPolicy {
+    /// As in ICPC, those on problems which were never solved add nothing
+    #[default]
+    SolvedOnly,
+    /// Every wrong try counts, solved or not
+    AllTries,
+}
+
+/// How the penalty column and exports write a penalty
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PenaltyFormat {
+    /// Whole minutes, easiest for tools to parse
+    #[default]
+    Minutes,
+    /// `H:MM`, hours may go past a day and nothing goes below `0:00`
+    HoursMinutes,
+}
+
+impl PenaltyFormat {
+    pub fn show(self, minutes: i64) -> String {
+        match self {
+            PenaltyFormat::Minutes => minutes.to_string(),
+            PenaltyFormat::HoursMinutes => {
+                let minutes = minutes.max(0);
+                format!("{}:{:02}", minutes / 60, minutes % 60)
+            }
+        }
+    }
+}
+
+/// How partial scores show up in the cells and the score column
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreDisplay {
+    /// The points as the judge gave them
+    #[default]
+    Absolute,
+    /// Percent of each problem's `max_scores`, which also makes problems
+    /// worth more weigh the same in the score ranking
+    Percent,
+}
+
+/// What the number in `AC / n` and `WA / n` counts, the same for both
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TryCount {
+    /// Every submission up to and including the shown verdict, as ICPC tries
+    #[default]
+    Tries,
+    /// Only the ones before the shown verdict
+    Before,
+}
+
+/// The `[start, end]` range of submission times a board counts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start: Option<DateTime<Local>>,
+    pub end: Option<DateTime<Local>>,
+}
+
+impl TimeWindow {
+    pub fn is_unbounded(&self) -> bool {
+        self.start.is_none() && self.end.is_none()
+    }
+
+    pub fn contains(&self, t: DateTime<Local>) -> bool {
+        self.start.is_none_or(|start| start <= t) && self.end.is_none_or(|end| t <= end)
+    }
+}
+
+/// What the name column shows for each user
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameDisplay {
+    #[default]
+    Real,
+    /// A hash of the user id, the same on every refresh
+    Anonymous,
+    Id,
+}
+
+impl NameDisplay {
+    pub fn show(self, uid: u32, name: &str) -> String {
+        match self {
+            // Not known to the judge, or not looked up yet
+            NameDisplay::Real if name.is_empty() => format!("User #{}", uid),
+            NameDisplay::Real => name.to_owned(),
+            NameDisplay::Anonymous => format!("User {:08x}", anonymize(&[], uid)),
+            NameDisplay::Id => format!("User #{}", uid),
+        }
+    }
+}
+
+/// FNV-1a, chosen over `DefaultHasher` as its output never changes
+fn anonymize(salt: &[u8], uid: u32) -> u32 {
+    salt.iter()
+        .chain(&uid.to_le_bytes())
+        .fold(0x811c_9dc5, |h, &b| {
+            (h ^ u32::from(b)).wrapping_mul(0x0100_0193)
+        })
+}
+
+/// Shared by the time settings, `key` names the one in the error
+fn parse_time(key: &str, value: &Option<String>) -> SimpleResult<Option<DateTime<Local>>> {
+    let s = match value {
+        Some(s) => s.trim(),
+        None => return Ok(None),
+    };
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(Some(t.with_timezone(&Local)));
+    }
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .map(Some)
+        .ok_or_else(|| SimpleError::Custom {
+            message: format!("Invalid {}: {}", key, s),
+        })
+}
+
+/// The timezone used when displaying times
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisplayTz {
+    Local,
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl DisplayTz {
+    /// Accept "Local", an IANA name like "Asia/Taipei", or an offset like "+08:00"
+    pub fn parse(s: &str) -> SimpleResult<Self> {
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(DisplayTz::Local);
+        }
+        if s.starts_with('+') || s.starts_with('-') {
+            return parse_offset(s)
+                .map(DisplayTz::Fixed)
+                .ok_or_else(|| SimpleError::Custom {
+                    message: format!("Invalid timezone offset: {}", s),
+                });
+        }
+        s.parse::<Tz>()
+            .map(DisplayTz::Named)
+            .map_err(|_| SimpleError::Custom {
+                message: format!("Unknown timezone: {}", s),
+            })
+    }
+
+    pub fn format(&self, t: &DateTime<Local>, fmt: &str) -> String {
+        match self {
+            DisplayTz::Local => t.format(fmt).to_string(),
+            DisplayTz::Fixed(offset) => t.with_timezone(offset).format(fmt).to_string(),
+            DisplayTz::Named(tz) => t.with_timezone(tz).format(fmt).to_string(),
+        }
+    }
+}
+
+fn parse_offset(s: &str) -> Option<FixedOffset> {
+    let sign = if s.starts_with('-') { -1 } else { 1 };
+    let digits: String = s[1..].chars().filter(|&c| c != ':').collect();
+    let valid_len = digits.len() == 2 || digits.len() == 4;
+    if !valid_len || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = if digits.len() == 4 {
+        digits[2..].parse().ok()?
+    } else {
+        0
+    };
+    if minutes >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_offset_rejects_non_digits() {
+        assert_eq!(parse_offset("+08:00"), FixedOffset::east_opt(8 * 3600));
+        assert_eq!(
+            parse_offset("-0530"),
+            FixedOffset::east_opt(-(5 * 3600 + 30 * 60))
+        );
+        assert_eq!(parse_offset("+aé1"), None);
+        assert_eq!(parse_offset("+0é"), None);
+        assert_eq!(parse_offset("+-100"), None);
+        assert_eq!(parse_offset("+08:60"), None);
+    }
+
+    #[test]
+    fn paging_defaults_to_everything() {
+        let meta: Metadata = toml::from_str("group_id = 1\nuser_token = \"\"").unwrap();
+        assert_eq!(meta.paging(), Paging::default());
+        assert_eq!(meta.paging().max_submissions, None);
+
+        let meta: Metadata = toml::from_str(
+            "group_id = 1\nuser_token = \"\"\npage_size = 50\nmax_submissions = 120",
+        )
+        .unwrap();
+        let paging = meta.paging();
+        assert_eq!((paging.page_size, paging.max_submissions), (50, Some(120)));
+        assert!(meta.validate().is_ok());
+
+        let meta: Metadata =
+            toml::from_str("group_id = 1\nuser_token = \"\"\npage_size = 0").unwrap();
+        assert!(meta.validate().is_err());
+    }
+
+    #[test]
+    fn refresh_backs_off_up_to_the_cap() {
+        let meta: Metadata = toml::from_str(
+            "group_id = 1\nuser_token = \"\"\nrefresh_seconds = 10\nrefresh_max_seconds = 60",
+        )
+        .unwrap();
+        let delays: Vec<u64> = (0..5)
+            .map(|quiet| meta.refresh_delay(quiet).unwrap().as_secs())
+            .collect();
+        assert_eq!(delays, vec![10, 20, 40, 60, 60]);
+        assert_eq!(meta.refresh_delay(u32::MAX).unwrap().as_secs(), 60);
+
+        let fixed: Metadata =
+            toml::from_str("group_id = 1\nuser_token = \"\"\nrefresh_seconds = 10").unwrap();
+        assert_eq!(fixed.refresh_delay(3).unwrap().as_secs(), 10);
+    }
+
+    #[test]
+    fn accepted_verdicts_take_judge_specific_ids() {
+        let meta: Metadata =
+            toml::from_str("group_id = 1\nuser_token = \"\"\naccepted_verdicts = [10, 12]")
+                .unwrap();
+        assert!(meta.validate().is_ok());
+        assert_eq!(meta.accepted_verdicts(), vec![10, 12]);
+
+        let pending: Metadata =
+            toml::from_str("group_id = 1\nuser_token = \"\"\naccepted_verdicts = [1]").unwrap();
+        assert!(pending.validate().is_err());
+    }
+
+    #[test]
+    fn pseudonyms_need_a_salt() {
+        let unsalted: Metadata = toml::from_str("group_id = 1\nuser_token = \"\"").unwrap();
+        assert!(unsalted.pseudonyms().is_err());
+        let empty: Metadata =
+            toml::from_str("group_id = 1\nuser_token = \"\"\npseudonym_salt = \"\"").unwrap();
+        assert!(empty.pseudonyms().is_err());
+
+        let meta: Metadata =
+            toml::from_str("group_id = 1\nuser_token = \"\"\npseudonym_salt = \"s3cret\"").unwrap();
+        let pseudonym = meta.pseudonyms().unwrap();
+        assert_eq!(pseudonym(7), pseudonym(7));
+        assert_ne!(pseudonym(7), pseudonym(8));
+        // Not to be lined up against the anonymous names on screen
+        assert_ne!(pseudonym(7), NameDisplay::Anonymous.show(7, ""));
+    }
+
+    #[test]
+    fn namespaced_columns_map_back_to_their_group() {
+        let merged: Metadata = toml::from_str("group_id = 1\nuser_token = \"\"").unwrap();
+        assert!(merged.problem_columns().is_none());
+        assert_eq!(merged.problem_label(105), "105");
+
+        let meta: Metadata = toml::from_str(
+            "group_id = 3\nuser_token = \"\"\nextra_groups = [7]\nnamespace_problems = true\n\
+             max_scores = { 105 = 50 }",
+        )
+        .unwrap();
+        assert!(meta.validate().is_ok());
+        let columns = meta.problem_columns().unwrap();
+        let (a, b) = (
+            columns.column(3, 105).unwrap(),
+            columns.column(7, 105).unwrap(),
+        );
+        assert_ne!(a, b);
+        assert_eq!(columns.origin(b), (7, 105));
+        assert_eq!(meta.problem_label(a), "G3/105");
+        assert_eq!(meta.problem_id(b), 105);
+        assert_eq!(meta.max_score(b), 50);
+        assert_eq!(columns.column(9, 105), None);
+        assert_eq!(columns.column(7, u32::MAX), None);
+
+        for extra in &[
+            "contest_id = 5",
+            "problem_list = [105]",
+            "include_problems = [105]",
+        ] {
+            let meta: Metadata = toml::from_str(&format!(
+                "group_id = 3\nuser_token = \"\"\nnamespace_problems = true\n{}",
+                extra
+            ))
+            .unwrap();
+            assert!(meta.validate().is_err(), "{}", extra);
+        }
+    }
+
+    #[test]
+    fn scores_scale_to_percent_of_the_max() {
+        let absolute: Metadata = toml::from_str("group_id = 1\nuser_token = \"\"").unwrap();
+        assert_eq!(absolute.scaled_score(101, 30), 30);
+
+        let meta: Metadata = toml::from_str(
+            "group_id = 1\nuser_token = \"\"\nscore_display = \"percent\"\n[max_scores]\n101 = 300",
+        )
+        .unwrap();
+        assert!(meta.check_max_scores().is_ok());
+        assert_eq!(meta.scaled_score(101, 150), 50);
+        assert_eq!(meta.scaled_score(101, 299), 99);
+        // Problems left out are out of 100
+        assert_eq!(meta.scaled_score(102, 40), 40);
+
+        let bad: Metadata =
+            toml::from_str("group_id = 1\nuser_token = \"\"\n[max_scores]\n101 = 0").unwrap();
+        assert!(bad.check_max_scores().is_err());
+    }
+
+    #[test]
+    fn missing_meta_is_written_as_the_default() {
+        let dir = std::env::temp_dir().join(format!("foj-meta-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(META_FILE);
+        let _ = fs::remove_file(&path);
+
+        // Asking the user to fill it in, so loading still fails
+        assert!(Metadata::load(Some(&path)).is_err());
+        assert!(path.exists());
+        assert_eq!(Metadata::read(Some(&path)).unwrap(), Metadata::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn minutes_round_at_the_sub_minute_boundary() {
+        let minutes =
+            |rounding: MinuteRounding, secs| rounding.minutes(chrono::Duration::seconds(secs));
+        let cases = [
+            (59, 0, 1, 1),
+            (60, 1, 1, 1),
+            (89, 1, 1, 2),
+            (90, 1, 2, 2),
+            (119, 1, 2, 2),
+        ];
+        for &(secs, floor, round, ceil) in cases.iter() {
+            assert_eq!(
+                minutes(MinuteRounding::Floor, secs),
+                floor,
+                "floor {}s",
+                secs
+            );
+            assert_eq!(
+                minutes(MinuteRounding::Round, secs),
+                round,
+                "round {}s",
+                secs
+            );
+            assert_eq!(minutes(MinuteRounding::Ceil, secs), ceil, "ceil {}s", secs);
+        }
+        let meta: Metadata =
+            toml::from_str("group_id = 1\nuser_token = \"\"\nminute_rounding = \"ceil\"").unwrap();
+        assert_eq!(meta.minute_rounding(), MinuteRounding::Ceil);
+        assert_eq!(Metadata::default().minute_rounding(), MinuteRounding::Floor);
+    }
+}
@@ -0,0 +1,12 @@
+//! The types most users of the crate need, for `use foj_scoreboard::prelude::*;`
+//!
+//! Settings enums and the rendering helpers stay in their own modules.
+
+pub use crate::api::{FojApi, Problem, Submission, Verdict};
+pub use crate::error::{SimpleError, SimpleResult};
+pub use crate::fake_term::{FakeTerm, FakeTermString};
+pub use crate::meta::{Metadata, TimeWindow};
+pub use crate::scoreboard::{
+    sync, CoverageSummary, ProblemOrigin, ProblemStats, ReportFormat, Scoreboard, SolveStatus,
+    SyncConfig,
+};
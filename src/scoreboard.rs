@@ -1,21 +1,122 @@
 use crate::api::*;
 use crate::error::*;
+use crate::meta::{
+    DisplayTz, Metadata, MinuteRounding, PenaltyFormat, PenaltyPolicy, ProblemColumns,
+    ProblemGroup, ScoreDisplay, SortField, SortOrder, TimeWindow, TryCount,
+};
+use crate::theme::BoardTheme;
 use chrono::prelude::*;
-use futures::future::Future;
-use prettytable::{format::Alignment, Cell, Row, Table};
+use chrono::Duration;
+use futures::future::{Either, Future};
+use prettytable::{color, format::Alignment, Attr, Cell, Row, Table};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use std::collections::{BTreeMap, BTreeSet};
-use std::fmt;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::{self, Write as _};
 use std::fs;
-use std::path::Path;
-use std::sync::{Arc, Mutex, RwLock};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Instant;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Scoreboard {
     user_map: Mutex<BTreeMap<u32, UserRecord>>,
     problem_set: Mutex<BTreeSet<u32>>,
+    problem_info: Mutex<BTreeMap<u32, Problem>>,
     cache_time: RwLock<DateTime<Local>>,
+    self_id: RwLock<Option<u32>>,
+    feed: Mutex<VecDeque<FeedEvent>>,
+    /// Counted submissions above `seen_through`, held back by one still
+    /// being judged
+    boundary_ids: Mutex<BTreeSet<u64>>,
+    fetch_count: RwLock<Option<FetchCount>>,
+    /// Every submission up to this id has been counted, see `save_submissions`
+    seen_through: RwLock<Option<u64>>,
+    /// Who solved each problem first, breaking ties for `first_solvers`
+    first_solver: Mutex<BTreeMap<u32, FirstSolve>>,
+    /// What interrupted fetches received, by `list_key`
+    checkpoints: Mutex<BTreeMap<String, Checkpoint>>,
+    /// Moved on by each sync which changed a cell, see `revision`
+    #[serde(skip)]
+    revision: AtomicUsize,
+    /// Held while a sync counts submissions and while the board is written,
+    /// as each field is locked on its own. Otherwise an autosave could pair
+    /// the users before a sync with the watermark after it, and the
+    /// submissions between them would never be counted.
+    #[serde(skip)]
+    sync_lock: Mutex<()>,
+}
+
+/// How many group and contest submissions the judge said it has against how
+/// many came back in the last sync, a gap hints at a paging problem
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FetchCount {
+    /// The `count` of the submission lists
+    pub reported: usize,
+    pub received: usize,
+}
+
+/// How many events the cache keeps for the activity feed
+const FEED_CAPACITY: usize = 1000;
+/// Minutes added to a solve for each wrong try before it, as in ICPC
+const PENALTY_MINUTES: i64 = 20;
+
+/// A verdict which changed a cell, in the order they were judged
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+struct FeedEvent {
+    time: DateTime<Local>,
+    user_id: u32,
+    problem_id: u32,
+    status: SolveStatus,
+}
+
+/// The earliest accepted submission of a problem
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct FirstSolve {
+    user_id: u32,
+    time: DateTime<Local>,
+    /// Breaks ties within a second, as those are counted in id order
+    submission_id: u64,
+}
+
+impl FirstSolve {
+    fn is_before(&self, other: &FirstSolve) -> bool {
+        (self.time, self.submission_id) < (other.time, other.submission_id)
+    }
+}
+
+/// The difference between two boards, as made by `Scoreboard::diff_patch`
+#[derive(Debug, Serialize, Deserialize)]
+struct Patch {
+    users: Vec<UserPatch>,
+    removed_users: Vec<u32>,
+    problem_set: Option<BTreeSet<u32>>,
+    problem_info: Option<BTreeMap<u32, Problem>>,
+    cache_time: DateTime<Local>,
+    self_id: Option<u32>,
+    feed: Option<VecDeque<FeedEvent>>,
+    boundary_ids: Option<BTreeSet<u64>>,
+    fetch_count: Option<FetchCount>,
+    seen_through: Option<u64>,
+    first_solver: Option<BTreeMap<u32, FirstSolve>>,
+    /// Always sent, as they hold no comparable state and are mostly empty
+    checkpoints: BTreeMap<String, Checkpoint>,
+}
+
+/// A user's changed cells, along with their current name
+#[derive(Debug, Serialize, Deserialize)]
+struct UserPatch {
+    uid: u32,
+    id: u32,
+    name: String,
+    name_missing: bool,
+    last_submit: Option<DateTime<Local>>,
+    notes: Option<String>,
+    cells: Vec<(u32, ProblemCell)>,
+    removed_cells: Vec<u32>,
 }
 
 impl Scoreboard {
@@ -23,175 +124,2493 @@ impl Scoreboard {
         Self {
             user_map: Mutex::new(BTreeMap::new()),
             problem_set: Mutex::new(BTreeSet::new()),
+            problem_info: Mutex::new(BTreeMap::new()),
             cache_time: RwLock::new(DateTime::<Local>::from(std::time::UNIX_EPOCH)),
+            self_id: RwLock::new(None),
+            feed: Mutex::new(VecDeque::new()),
+            boundary_ids: Mutex::new(BTreeSet::new()),
+            fetch_count: RwLock::new(None),
+            seen_through: RwLock::new(None),
+            first_solver: Mutex::new(BTreeMap::new()),
+            checkpoints: Mutex::new(BTreeMap::new()),
+            revision: AtomicUsize::new(0),
+            sync_lock: Mutex::new(()),
         }
     }
 
+    /// A board of `users` by `problems`, about 80% of the cells tried and
+    /// most of those solved after a wrong try, for tests and benchmarks
+    #[doc(hidden)]
+    pub fn synthetic(users: u32, problems: u32) -> SimpleResult<Arc<Self>> {
+        let mut subs = Vec::new();
+        for uid in 1..=users {
+            for pid in 1..=problems {
+                let tries: &[(Verdict, i64)] = match (uid * 7 + pid * 3) % 5 {
+                    0 => &[],
+                    1 => &[(Verdict::WA, 0)],
+                    _ => &[(Verdict::WA, 0), (Verdict::AC, 60)],
+                };
+                for &(verdict_id, minutes) in tries {
+                    let time = Local.timestamp(1_600_000_000 + i64::from(pid) * minutes, 0);
+                    subs.push(Submission {
+                        memory_usage: None,
+                        time_usage: None,
+                        length: 0,
+                        verdict_id,
+                        execute_id: 0,
+                        user_id: uid,
+                        problem_id: pid,
+                        created_at: time,
+                        updated_at: time,
+                        id: subs.len() as u64 + 1,
+                        score: None,
+                    });
+                }
+            }
+        }
+        let board = Arc::new(Self::new());
+        let (accepted, rejected) = (Verdict::AC.id(), Verdict::WA.id());
+        save_submissions(
+            board.clone(),
+            subs,
+            Duration::zero(),
+            &[accepted],
+            &[rejected],
+        )?;
+        for (uid, user) in lock(&board.user_map).iter_mut() {
+            user.name = format!("user{}", uid);
+        }
+        Ok(board)
+    }
+
     pub fn load_cache<P: AsRef<Path>>(path: P) -> SimpleResult<Self> {
-        let f = fs::OpenOptions::new().read(true).open(path)?;
-        Ok(bincode::deserialize_from(f)?)
+        let bytes = if is_std_stream(path.as_ref()) {
+            let mut bytes = Vec::new();
+            io::stdin().lock().read_to_end(&mut bytes)?;
+            bytes
+        } else {
+            fs::read(path)?
+        };
+        let e = match decode_exact::<Self>(&bytes) {
+            Ok(board) => return Ok(board),
+            Err(e) => e,
+        };
+        // Upgrading keeps the board, what the first layout lacked starts empty
+        if let Ok(board) = decode_exact::<FirstCache>(&bytes) {
+            info!("Loaded a cache in the first layout");
+            return Ok(board.into());
+        }
+        Err(e)
     }
 
+    /// Write the board as one snapshot, never halfway through a sync
     pub fn save_cache<P: AsRef<Path>>(&self, path: P) -> SimpleResult<()> {
-        let f = fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(path)?;
-        bincode::serialize_into(f, self)?;
+        let _sync = lock(&self.sync_lock);
+        if is_std_stream(path.as_ref()) {
+            // Cursive draws on /dev/tty and logs into its own buffer,
+            // so nothing else is written to stdout
+            let stdout = io::stdout();
+            let mut lock = stdout.lock();
+            bincode::serialize_into(&mut lock, self)?;
+            lock.flush()?;
+            return Ok(());
+        }
+        // Written aside and renamed over, a crash mid-save keeps the old cache
+        let path = path.as_ref();
+        let bytes = bincode::serialize(self)?;
+        let tmp = temp_path(path);
+        fs::write(&tmp, bytes)?;
+        if let Err(e) = fs::rename(&tmp, path) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e.into());
+        }
         Ok(())
     }
 
-    pub fn gen_table(&self, problems: Option<&[u32]>) -> Table {
-        let mut table = Table::new();
-        let user_lock = self.user_map.lock().unwrap();
-        let mut users: Vec<&UserRecord> = user_lock.iter().map(|p| p.1).collect();
-        let problems_lock = self.problem_set.lock().unwrap();
+    /// Encode only what changed since `base`, see `apply_patch`
+    pub fn diff_patch(&self, base: &Scoreboard) -> SimpleResult<Vec<u8>> {
+        if std::ptr::eq(self, base) {
+            return Err(SimpleError::Custom {
+                message: "Cannot diff a board against itself".to_owned(),
+            });
+        }
+        // Taken in the order `save_submissions` takes them, the fields it
+        // holds across the sync come before `user_map`
+        let mut patch = Patch {
+            users: Vec::new(),
+            removed_users: Vec::new(),
+            problem_set: None,
+            problem_info: None,
+            cache_time: self.cache_time(),
+            self_id: *read(&self.self_id),
+            feed: None,
+            boundary_ids: None,
+            fetch_count: self.fetch_count(),
+            seen_through: *read(&self.seen_through),
+            first_solver: None,
+            checkpoints: BTreeMap::new(),
+        };
+        patch.checkpoints = lock(&self.checkpoints).clone();
+        let boundary = lock(&self.boundary_ids).clone();
+        if boundary != *lock(&base.boundary_ids) {
+            patch.boundary_ids = Some(boundary);
+        }
+        let user_lock = lock(&self.user_map);
+        let base_users = lock(&base.user_map);
 
-        users.sort_by(|&a, &b| b.ac_count(&problems_lock).cmp(&a.ac_count(&problems_lock)));
+        for (&uid, user) in user_lock.iter() {
+            let base_user = base_users.get(&uid);
+            let cells: Vec<(u32, ProblemCell)> = user
+                .problems
+                .iter()
+                .filter(|&(pid, cell)| base_user.and_then(|b| b.problems.get(pid)) != Some(cell))
+                .map(|(&pid, &cell)| (pid, cell))
+                .collect();
+            let removed_cells: Vec<u32> = base_user
+                .iter()
+                .flat_map(|b| b.problems.keys())
+                .filter(|pid| !user.problems.contains_key(pid))
+                .copied()
+                .collect();
+            let same_info = matches!(base_user, Some(b)
+                if b.id == user.id
+                    && b.name == user.name
+                    && b.name_missing == user.name_missing
+                    && b.last_submit == user.last_submit
+                    && b.notes == user.notes);
+            if same_info && cells.is_empty() && removed_cells.is_empty() {
+                continue;
+            }
+            patch.users.push(UserPatch {
+                uid,
+                id: user.id,
+                name: user.name.clone(),
+                name_missing: user.name_missing,
+                last_submit: user.last_submit,
+                notes: user.notes.clone(),
+                cells,
+                removed_cells,
+            });
+        }
+        patch.removed_users = base_users
+            .keys()
+            .filter(|uid| !user_lock.contains_key(uid))
+            .copied()
+            .collect();
 
-        // Generate the actual problem list
-        let prob_list: Cow<[u32]> = if let Some(problems) = problems {
-            Cow::from(problems)
+        let problems = lock(&self.problem_set);
+        if *problems != *lock(&base.problem_set) {
+            patch.problem_set = Some(problems.clone());
+        }
+        let info = lock(&self.problem_info);
+        if *info != *lock(&base.problem_info) {
+            patch.problem_info = Some(info.clone());
+        }
+        let feed = lock(&self.feed);
+        if *feed != *lock(&base.feed) {
+            patch.feed = Some(feed.clone());
+        }
+        let first = lock(&self.first_solver);
+        if *first != *lock(&base.first_solver) {
+            patch.first_solver = Some(first.clone());
+        }
+        Ok(bincode::serialize(&patch)?)
+    }
+
+    /// Bring a copy of the base board up to date with a `diff_patch` result
+    pub fn apply_patch(&mut self, patch: &[u8]) -> SimpleResult<()> {
+        let patch: Patch = bincode::deserialize(patch)?;
+        let users = self
+            .user_map
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        for uid in &patch.removed_users {
+            users.remove(uid);
+        }
+        for up in patch.users {
+            let user = users.entry(up.uid).or_default();
+            user.id = up.id;
+            user.name = up.name;
+            user.name_missing = up.name_missing;
+            user.last_submit = up.last_submit;
+            user.notes = up.notes;
+            for pid in &up.removed_cells {
+                user.problems.remove(pid);
+            }
+            user.problems.extend(up.cells);
+        }
+        if let Some(problems) = patch.problem_set {
+            *self
+                .problem_set
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner) = problems;
+        }
+        if let Some(info) = patch.problem_info {
+            *self
+                .problem_info
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner) = info;
+        }
+        if let Some(feed) = patch.feed {
+            *self.feed.get_mut().unwrap_or_else(PoisonError::into_inner) = feed;
+        }
+        if let Some(ids) = patch.boundary_ids {
+            *self
+                .boundary_ids
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner) = ids;
+        }
+        if let Some(first) = patch.first_solver {
+            *self
+                .first_solver
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner) = first;
+        }
+        *self
+            .cache_time
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = patch.cache_time;
+        *self
+            .self_id
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = patch.self_id;
+        *self
+            .fetch_count
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = patch.fetch_count;
+        *self
+            .seen_through
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = patch.seen_through;
+        *self
+            .checkpoints
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner) = patch.checkpoints;
+        Ok(())
+    }
+
+    /// Save a copy of the board into `dir`, named by its `cache_time`
+    pub fn save_snapshot<P: AsRef<Path>>(&self, dir: P) -> SimpleResult<PathBuf> {
+        fs::create_dir_all(&dir)?;
+        let name = format!("{}.cache", self.cache_time().format("%Y%m%d-%H%M%S"));
+        let path = dir.as_ref().join(name);
+        self.save_cache(&path)?;
+        Ok(path)
+    }
+
+    /// Invariants a cache should hold, one line per violation found
+    pub fn check_integrity(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let now = Local::now();
+        let cache_time = *read(&self.cache_time);
+        if cache_time > now {
+            problems.push(format!("cache time {} is in the future", cache_time));
+        }
+        let user_lock = lock(&self.user_map);
+        let problem_set = lock(&self.problem_set);
+        for (uid, user) in user_lock.iter() {
+            match user.last_submit {
+                None if !user.problems.is_empty() => {
+                    problems.push(format!("user {} has cells but no last submit", uid))
+                }
+                Some(at) if at > now => {
+                    problems.push(format!("user {} last submitted in the future", uid))
+                }
+                _ => {}
+            }
+            for (pid, cell) in &user.problems {
+                let mut bad = |what: &str| {
+                    problems.push(format!("user {} problem {}: {}", uid, pid, what));
+                };
+                if !problem_set.contains(pid) {
+                    bad("problem missing from the problem set");
+                }
+                match cell.status {
+                    SolveStatus::Accepted => {
+                        if cell.solved_at.is_none() {
+                            bad("accepted without a solve time");
+                        }
+                        if cell.attempts < cell.wa_count + 1 {
+                            bad("fewer attempts than wrong answers plus the AC");
+                        }
+                    }
+                    SolveStatus::WrongAnswer => {
+                        if cell.wa_count == 0 {
+                            bad("wrong answer without any wrong tries");
+                        }
+                        if cell.attempts < cell.wa_count {
+                            bad("fewer attempts than wrong answers");
+                        }
+                    }
+                    SolveStatus::Attempted | SolveStatus::None => {
+                        if cell.wa_count > 0 {
+                            bad("wrong tries without a wrong answer");
+                        }
+                    }
+                    SolveStatus::Frozen => bad("frozen cell saved in the cache"),
+                }
+                if cell.status != SolveStatus::Accepted && cell.solved_at.is_some() {
+                    bad("solve time without an AC");
+                }
+            }
+        }
+        for ev in lock(&self.feed).iter() {
+            if !user_lock.contains_key(&ev.user_id) || !problem_set.contains(&ev.problem_id) {
+                problems.push(format!(
+                    "feed event for unknown user {} or problem {}",
+                    ev.user_id, ev.problem_id
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Whether no submissions have been recorded at all
+    pub fn is_empty(&self) -> bool {
+        lock(&self.user_map).is_empty()
+    }
+
+    pub fn cache_time(&self) -> DateTime<Local> {
+        *read(&self.cache_time)
+    }
+
+    /// The checkpoint of a submission list, kept in the cache until the list
+    /// has been fetched in full
+    fn checkpoint(&self, key: &str) -> Checkpoint {
+        let mut checkpoints = lock(&self.checkpoints);
+        if let Some(checkpoint) = checkpoints.get(key) {
+            return checkpoint.clone();
+        }
+        let checkpoint = Checkpoint::default();
+        checkpoints.insert(key.to_owned(), checkpoint.clone());
+        checkpoint
+    }
+
+    /// Differs after a sync which changed any cell, starting over on load
+    pub fn revision(&self) -> usize {
+        self.revision.load(AtomicOrdering::SeqCst)
+    }
+
+    /// See `FetchCount`, `None` before the first sync
+    pub fn fetch_count(&self) -> Option<FetchCount> {
+        *read(&self.fetch_count)
+    }
+
+    /// The user's name as fetched from the judge, empty when unknown
+    pub fn user_name(&self, uid: u32) -> String {
+        lock(&self.user_map)
+            .get(&uid)
+            .map(|user| user.name.clone())
+            .unwrap_or_default()
+    }
+
+    /// What was written about the user with `set_note`
+    pub fn note(&self, uid: u32) -> Option<String> {
+        lock(&self.user_map)
+            .get(&uid)
+            .and_then(|user| user.notes.clone())
+    }
+
+    /// Attach a note to the user, a blank one removes it
+    pub fn set_note(&self, uid: u32, note: &str) -> SimpleResult<()> {
+        let mut user_lock = lock(&self.user_map);
+        let user = user_lock.get_mut(&uid).ok_or_else(|| SimpleError::Custom {
+            message: format!("No user with id {}", uid),
+        })?;
+        let note = note.trim();
+        user.notes = if note.is_empty() {
+            None
+        } else {
+            Some(note.to_owned())
+        };
+        Ok(())
+    }
+
+    /// Number of distinct users who solved the problem, repeated ACs count once
+    pub fn solve_count(&self, pid: u32) -> usize {
+        lock(&self.user_map)
+            .values()
+            .filter_map(|user| user.problems.get(&pid))
+            .filter(|cell| cell.status == SolveStatus::Accepted)
+            .count()
+    }
+
+    /// Number of judged submissions to the problem, every one of them counts
+    pub fn attempt_count(&self, pid: u32) -> usize {
+        lock(&self.user_map)
+            .values()
+            .filter_map(|user| user.problems.get(&pid))
+            .map(|cell| cell.attempts)
+            .sum()
+    }
+
+    /// Map each user id to the problems they got accepted after `since`
+    pub fn ac_since(&self, since: DateTime<Local>) -> BTreeMap<u32, Vec<u32>> {
+        lock(&self.user_map)
+            .iter()
+            .filter_map(|(&uid, user)| {
+                let solved: Vec<u32> = user
+                    .problems
+                    .iter()
+                    .filter(|(_, cell)| matches!(cell.solved_at, Some(t) if t > since))
+                    .map(|(&pid, _)| pid)
+                    .collect();
+                if solved.is_empty() {
+                    None
+                } else {
+                    Some((uid, solved))
+                }
+            })
+            .collect()
+    }
+
+    /// A problem is orphaned if it shows up in submissions but is no longer in the group
+    fn is_orphan(info: &BTreeMap<u32, Problem>, pid: u32) -> bool {
+        !info.is_empty() && !info.contains_key(&pid)
+    }
+
+    /// Which of the problem list and the submissions know about `pid`
+    pub fn problem_origin(&self, pid: u32) -> Option<ProblemOrigin> {
+        let problems_lock = lock(&self.problem_set);
+        let info_lock = lock(&self.problem_info);
+        Self::origin(&info_lock, &problems_lock, pid)
+    }
+
+    fn origin(
+        info: &BTreeMap<u32, Problem>,
+        problems: &BTreeSet<u32>,
+        pid: u32,
+    ) -> Option<ProblemOrigin> {
+        match (info.contains_key(&pid), problems.contains(&pid)) {
+            (true, true) => Some(ProblemOrigin::Both),
+            (true, false) => Some(ProblemOrigin::List),
+            (false, true) if Self::is_orphan(info, pid) => Some(ProblemOrigin::Submissions),
+            // Without a problem list there is nothing to disagree with
+            (false, true) => Some(ProblemOrigin::Both),
+            (false, false) => None,
+        }
+    }
+
+    /// Help organizers spot groups whose problem list doesn't match the submissions
+    fn log_problem_mismatch(&self) {
+        let problems_lock = lock(&self.problem_set);
+        let info_lock = lock(&self.problem_info);
+        let (mut listed, mut unlisted) = (Vec::new(), Vec::new());
+        for &pid in info_lock.keys().chain(problems_lock.iter()) {
+            match Self::origin(&info_lock, &problems_lock, pid) {
+                Some(ProblemOrigin::List) => listed.push(pid),
+                Some(ProblemOrigin::Submissions) => unlisted.push(pid),
+                _ => {}
+            }
+        }
+        if !listed.is_empty() {
+            debug!("Problems listed but never submitted to: {:?}", listed);
+        }
+        if !unlisted.is_empty() {
+            debug!(
+                "Problems submitted to but missing from the list: {:?}",
+                unlisted
+            );
+        }
+    }
+
+    /// The problem columns `gen_table` shows, in order
+    pub fn displayed_problems(&self, meta: &Metadata) -> Vec<u32> {
+        let problems_lock = lock(&self.problem_set);
+        let info_lock = lock(&self.problem_info);
+        Self::problem_list(meta, &problems_lock, &info_lock)
+    }
+
+    fn problem_list(
+        meta: &Metadata,
+        problem_set: &BTreeSet<u32>,
+        info: &BTreeMap<u32, Problem>,
+    ) -> Vec<u32> {
+        let mut prob_list: Vec<u32> = if let Some(problems) = meta.problems() {
+            problems.to_vec()
+        } else if meta.problems_from_api() && !info.is_empty() {
+            // Unattempted problems included, hidden ones left out
+            info.iter()
+                .filter(|(_, p)| p.visible || p.group_read)
+                .map(|(&pid, _)| pid)
+                .collect()
         } else {
-            let set_list: Vec<u32> = problems_lock.iter().copied().collect();
-            Cow::from(set_list)
+            problem_set.iter().copied().collect()
         };
+        if meta.hide_orphans() {
+            prob_list.retain(|&pid| !Self::is_orphan(info, pid));
+        }
+        // An explicit problem list always wins over untitled problems
+        if meta.hide_untitled() && meta.problems().is_none() {
+            prob_list.retain(|pid| !matches!(info.get(pid), Some(p) if p.title.trim().is_empty()));
+        }
+        // Forced problems show up even without submissions, exclusions always win
+        for &pid in meta.include_problems() {
+            if !prob_list.contains(&pid) {
+                prob_list.push(pid);
+            }
+        }
+        if meta.problems().is_none() {
+            prob_list.sort_unstable();
+        }
+        prob_list.retain(|&pid| !meta.exclude_problems().contains(&meta.problem_id(pid)));
+        prob_list
+    }
+
+    /// Put `users` in board order, following `Metadata::sort_keys`
+    ///
+    /// Only the problems in `prob_list` count, as shown by `problem_list`.
+    fn rank_users(meta: &Metadata, prob_list: &[u32], users: &mut [(&u32, &UserRecord)]) {
+        let sort_keys = meta.sort_keys();
+        let start = meta.contest_start().unwrap_or(None);
+        let rounding = meta.minute_rounding();
+        let policy = meta.penalty_policy();
+        users.sort_by(|&(a_uid, a), &(b_uid, b)| {
+            sort_keys
+                .iter()
+                .fold(Ordering::Equal, |ord, sort| {
+                    ord.then_with(|| {
+                        let ord = match sort.key {
+                            SortField::AcCount => a.ac_count(prob_list).cmp(&b.ac_count(prob_list)),
+                            SortField::LastSolve => a
+                                .last_solve_time(prob_list)
+                                .cmp(&b.last_solve_time(prob_list)),
+                            SortField::LastSubmit => a.last_submit.cmp(&b.last_submit),
+                            // Checked by `Metadata::load`, so only a hand-built meta has no start
+                            SortField::Penalty => match start {
+                                Some(start) => a
+                                    .penalty(prob_list, start, rounding, policy)
+                                    .cmp(&b.penalty(prob_list, start, rounding, policy)),
+                                None => Ordering::Equal,
+                            },
+                            SortField::Score => {
+                                a.score(prob_list, meta).cmp(&b.score(prob_list, meta))
+                            }
+                            SortField::Name => a.name.cmp(&b.name),
+                            SortField::UserId => a_uid.cmp(b_uid),
+                        };
+                        match sort.order {
+                            SortOrder::Asc => ord,
+                            SortOrder::Desc => ord.reverse(),
+                        }
+                    })
+                })
+                // Equal users would otherwise keep whatever order they came in
+                .then_with(|| a_uid.cmp(b_uid))
+        });
+    }
+
+    /// The users `gen_table` shows a row for, in their order
+    ///
+    /// Those without any shown cell are left out. Ranks count the rows left
+    /// out by `filter` and `search` as well, but not those below `min_ac`
+    /// unless `global_rank` is set.
+    fn board_rows<'a>(
+        meta: &'a Metadata,
+        users: &'a BTreeMap<u32, UserRecord>,
+        prob_list: &[u32],
+        self_id: Option<u32>,
+        opts: &TableOptions,
+    ) -> Vec<BoardRow<'a>> {
+        let mut ranked: Vec<(&u32, &UserRecord)> = counted_users(meta, users).collect();
+        Self::rank_users(meta, prob_list, &mut ranked);
+
+        // Then those who tried it, most attempts first, the rest keep their order
+        if let Some(pid) = opts.focus {
+            ranked.sort_by_key(|(_, user)| {
+                let cell = user.problems.get(&pid).copied().unwrap_or_default();
+                match cell.solved_at {
+                    Some(at) if cell.status == SolveStatus::Accepted => {
+                        (false, Some(at), Reverse(0))
+                    }
+                    _ => (true, None, Reverse(cell.attempts)),
+                }
+            });
+        }
+
+        let shared = shared_names(meta, users);
+        let search = opts.search.map(str::to_lowercase);
+        let mut rank = 0;
+        let mut rows = Vec::new();
+        for (&uid, user) in ranked {
+            // Make all 'NS' not display
+            let has_row = prob_list.iter().any(|pid| {
+                user.problems.get(pid).is_some_and(|p| {
+                    p.status != SolveStatus::None || (p.pending && meta.show_pending())
+                })
+            });
+            if !has_row {
+                continue;
+            }
+            let below_min =
+                matches!(meta.min_ac(), Some(k) if user.solved_among(prob_list.iter()) < k);
+            let ranked = !below_min || meta.global_rank();
+            if ranked {
+                rank += 1;
+            }
+            let pinned = below_min || matches!(meta.top_n(), Some(n) if rank > n);
+            if pinned && (!meta.pin_self() || self_id != Some(uid)) {
+                continue;
+            }
+            if let Some((pid, filter)) = opts.filter {
+                let status = user
+                    .problems
+                    .get(&pid)
+                    .map(|p| p.status)
+                    .unwrap_or_default();
+                if !filter.matches(status) {
+                    continue;
+                }
+            }
+            if let Some(query) = &search {
+                let name = display_name(meta, &shared, uid, &user.name);
+                if !name.to_lowercase().contains(query.as_str()) {
+                    continue;
+                }
+            }
+            rows.push(BoardRow {
+                user_id: uid,
+                user,
+                rank: Some(rank).filter(|_| ranked),
+                pinned,
+            });
+        }
+        rows
+    }
+
+    /// Like `gen_table`, then let `hook` adjust the table before `print_term`
+    pub fn gen_table_with<F>(&self, meta: &Metadata, opts: &TableOptions, hook: F) -> Table
+    where
+        F: FnOnce(&mut Table),
+    {
+        let mut table = self.gen_table(meta, opts);
+        hook(&mut table);
+        table
+    }
+
+    /// The board, narrowed and reordered as `opts` says
+    pub fn gen_table(&self, meta: &Metadata, opts: &TableOptions) -> Table {
+        let focus = opts.focus;
+        let mut table = Table::new();
+        // Computed first, as it takes the user lock on its own
+        let stats = if meta.show_solve_stats() || meta.show_solve_ratio() {
+            Some(self.problem_stats(meta))
+        } else {
+            None
+        };
+        let user_lock = lock(&self.user_map);
+        let user_map = visible_users(meta, &user_lock);
+        let counted: Vec<(&u32, &UserRecord)> = counted_users(meta, &user_map).collect();
+        let shared = shared_names(meta, &user_map);
+        let problems_lock = lock(&self.problem_set);
+        let info_lock = lock(&self.problem_info);
+        let stars = first_solvers(&counted, &lock(&self.first_solver));
+        let theme = meta.theme().unwrap_or_default();
+
+        // Generate the actual problem list
+        let prob_list = Self::problem_list(meta, &problems_lock, &info_lock);
         debug!("{:?}", prob_list);
 
+        let self_id = *read(&self.self_id);
+        let rows = Self::board_rows(meta, &user_map, &prob_list, self_id, opts);
+
+        // Lay out the columns, grouping problems when configured
+        let groups = meta.problem_groups();
+        let mut columns = layout_columns(&prob_list, groups);
+        let start = meta.contest_start().unwrap_or(None);
+        if meta.show_scores() {
+            columns.push(Column::Score);
+        }
+        if meta.show_penalty() && start.is_some() {
+            columns.push(Column::Penalty);
+        }
+        if meta.show_last_submit() {
+            columns.push(Column::LastSubmit);
+        }
+
+        // Generate problem groups' name
+        if !groups.is_empty() {
+            let mut group_cells = vec![cell!("")];
+            let mut rest = columns.len();
+            for (i, group) in groups.iter().enumerate() {
+                let width = columns
+                    .iter()
+                    .filter(|col| match col {
+                        Column::Problem(pid) => group.problems.contains(pid),
+                        Column::Subtotal(g) => *g == i,
+                        Column::Total | Column::Score | Column::Penalty | Column::LastSubmit => {
+                            false
+                        }
+                    })
+                    .count();
+                let mut c = cell!(c->group.name);
+                c.set_hspan(width);
+                group_cells.push(c);
+                rest -= width;
+            }
+            let mut c = cell!("");
+            c.set_hspan(rest);
+            group_cells.push(c);
+            table.add_row(Row::new(group_cells));
+        }
+
+        // Looked up once per column rather than per cell
+        let balloons: Vec<Option<color::Color>> = columns
+            .iter()
+            .map(|col| match col {
+                Column::Problem(pid) => meta.balloon(*pid),
+                _ => None,
+            })
+            .collect();
+
         // Generate problems' ID
-        let mut prob_cells = Vec::new();
+        let mut prob_cells = Vec::with_capacity(columns.len() + 1);
         prob_cells.push(cell!(""));
-        for prob in prob_list.iter() {
-            prob_cells.push(cell!(c->prob));
+        for (col, balloon) in columns.iter().zip(&balloons) {
+            let prob = match col {
+                Column::Problem(pid) => pid,
+                Column::Subtotal(_) => {
+                    prob_cells.push(cell!(c->"Sum"));
+                    continue;
+                }
+                Column::Total => {
+                    prob_cells.push(cell!(c->"Total"));
+                    continue;
+                }
+                Column::Score => {
+                    prob_cells.push(match meta.score_display() {
+                        ScoreDisplay::Absolute => cell!(c->"Score"),
+                        ScoreDisplay::Percent => cell!(c->"Score %"),
+                    });
+                    continue;
+                }
+                Column::Penalty => {
+                    prob_cells.push(cell!(c->"Penalty"));
+                    continue;
+                }
+                Column::LastSubmit => {
+                    prob_cells.push(cell!(c->"Last Submit"));
+                    continue;
+                }
+            };
+            let label = meta.problem_label(*prob);
+            let mut c = match Self::origin(&info_lock, &problems_lock, *prob) {
+                Some(ProblemOrigin::Submissions) => cell!(c->format!("?{}", label)),
+                // Nobody has tried it yet, or the group cannot submit to it
+                Some(ProblemOrigin::List) => {
+                    let mut c = cell!(c->label);
+                    c.style(Attr::ForegroundColor(theme.ns));
+                    c
+                }
+                _ => cell!(c->label),
+            };
+            if let Some(balloon) = *balloon {
+                c.style(Attr::Bold);
+                c.style(Attr::ForegroundColor(balloon));
+            }
+            if focus == Some(*prob) {
+                c.style(Attr::Underline(true));
+            }
+            prob_cells.push(c);
         }
         table.add_row(Row::new(prob_cells.clone()));
 
+        if meta.show_source() {
+            let mut source_cells = vec![cell!(c->"Source")];
+            for col in columns.iter() {
+                let source = match col {
+                    Column::Problem(pid) => info_lock.get(pid).map(|p| p.source.trim()),
+                    _ => None,
+                };
+                source_cells.push(cell!(c->source.unwrap_or_default()));
+            }
+            table.add_row(Row::new(source_cells));
+        }
+
         // Generate Update Time
         let mut update_row = Vec::new();
         update_row.push(cell!(c->"Updated At"));
 
-        let t = self.cache_time.read().unwrap();
-        let mut update_cell = Cell::new_align(
-            format!("{}\n{}", t.format("%Y-%m-%d"), t.format("%H:%M:%S")).as_str(),
-            Alignment::CENTER,
-        );
-        update_cell.set_hspan(prob_list.len());
+        let t = read(&self.cache_time);
+        let tz = meta.timezone().unwrap_or(DisplayTz::Local);
+        let mut update_text = tz.format(&t, "%Y-%m-%d\n%H:%M:%S");
+        if let Some(count) = self.fetch_count().filter(|_| meta.show_fetch_count()) {
+            let _ = write!(
+                update_text,
+                "\n{} of {} fetched",
+                count.received, count.reported
+            );
+        }
+        let mut update_cell = Cell::new_align(&update_text, Alignment::CENTER);
+        update_cell.set_hspan(columns.len());
         update_row.push(update_cell);
 
         table.add_row(Row::new(update_row));
 
         // Generate User Solving Status
-        for user in &users {
-            let mut cells = Vec::new();
-            let mut should_display = false;
-            cells.push(cell!(c->user.name));
-            for prob in prob_list.iter() {
-                let p = &user.problems.get(&prob).copied().unwrap_or_default();
-                // Make all 'NS' not display
+        let now = Local::now();
+        let recent = Duration::minutes(meta.recent_minutes());
+        let shown = rows.len();
+        // Reused by every cell below, instead of a new String each
+        let mut text = String::new();
+        for (i, row) in rows.iter().enumerate() {
+            let (uid, user) = (row.user_id, row.user);
+            let stripe = Some(theme.stripe).filter(|_| meta.stripe_rows() && i % 2 == 1);
+            // Set first, so a cell giving its own background keeps it
+            let plain = |text: &str| {
+                let c = Cell::new_align(text, Alignment::CENTER);
+                match stripe {
+                    Some(bg) => c.with_style(Attr::BackgroundColor(bg)),
+                    None => c,
+                }
+            };
+            let colored_cell = |text: &str, fg| plain(text).with_style(Attr::ForegroundColor(fg));
+            let mut cells = Vec::with_capacity(columns.len() + 1);
+            let mut name = display_name(meta, &shared, uid, &user.name);
+            // Flags the rows which have a note, read with the 'n' key
+            if user.notes.is_some() {
+                name.push('*');
+            }
+            cells.push(plain(&name));
+            for (col, balloon) in columns.iter().zip(&balloons) {
+                let prob = match col {
+                    Column::Problem(pid) => pid,
+                    Column::Subtotal(g) => {
+                        let solved = user.solved_among(
+                            groups[*g]
+                                .problems
+                                .iter()
+                                .filter(|pid| prob_list.contains(pid)),
+                        );
+                        cells.push(plain(&solved.to_string()));
+                        continue;
+                    }
+                    Column::Total => {
+                        cells.push(plain(&user.solved_among(prob_list.iter()).to_string()));
+                        continue;
+                    }
+                    Column::Score => {
+                        cells.push(plain(&user.score(&prob_list, meta).to_string()));
+                        continue;
+                    }
+                    Column::Penalty => {
+                        let penalty = start.map_or(0, |start| {
+                            user.penalty(
+                                &prob_list,
+                                start,
+                                meta.minute_rounding(),
+                                meta.penalty_policy(),
+                            )
+                        });
+                        cells.push(plain(&meta.penalty_format().show(penalty)));
+                        continue;
+                    }
+                    Column::LastSubmit => {
+                        cells.push(match user.last_submit {
+                            Some(at) if now - at <= recent => {
+                                colored_cell(&tz.format(&at, "%m-%d %H:%M"), theme.ac)
+                            }
+                            Some(at) => plain(&tz.format(&at, "%m-%d %H:%M")),
+                            None => plain(""),
+                        });
+                        continue;
+                    }
+                };
+                let p = &user.problems.get(prob).copied().unwrap_or_default();
+                text.clear();
+                let pending = p.pending && meta.show_pending();
                 let c = match p.status {
                     SolveStatus::Accepted => {
-                        should_display = true;
-                        cell!(Fgc->format!("{} / {}", p.status, p.wa_count + 1))
+                        let _ = write!(text, "{} / {}", p.status, p.tries(meta.try_count()));
+                        let first = stars.get(prob) == Some(&uid);
+                        if first {
+                            text.push_str(" ★");
+                        }
+                        let balloon = balloon.filter(|_| meta.balloon_ac_cells());
+                        let mut c = colored_cell(&text, balloon.unwrap_or(theme.ac));
+                        if first {
+                            c.style(Attr::Bold);
+                        }
+                        c
                     }
                     SolveStatus::WrongAnswer => {
-                        should_display = true;
-                        cell!(Frc->format!("{} / {}", p.status, p.wa_count))
+                        let partial = p.best_score.filter(|&score| score > 0);
+                        let shown = partial.filter(|_| meta.show_scores());
+                        match shown {
+                            Some(score) => {
+                                let _ = write!(text, "{}", meta.scaled_score(*prob, score));
+                                if meta.score_display() == ScoreDisplay::Percent {
+                                    text.push('%');
+                                }
+                            }
+                            None => {
+                                let status = p.status_text(meta.show_verdicts());
+                                let _ = write!(text, "{} / {}", status, p.tries(meta.try_count()));
+                            }
+                        }
+                        if pending {
+                            text.push_str(" ?");
+                            colored_cell(&text, theme.pending)
+                        } else if let Some(score) = shown {
+                            colored_cell(&text, score_color(&theme, meta, *prob, score))
+                        } else {
+                            colored_cell(&text, theme.wa)
+                        }
+                    }
+                    // Every try is shown, only the verdicts are kept back
+                    SolveStatus::Frozen => {
+                        let _ = write!(text, "? / {}", p.attempts.max(1));
+                        colored_cell(&text, theme.pending)
                     }
-                    SolveStatus::None => cell!(FDc->format!("{}", p.status)),
+                    _ if pending => colored_cell("?", theme.pending),
+                    SolveStatus::Attempted => colored_cell(p.status.abbr(), theme.attempted),
+                    SolveStatus::None => colored_cell(p.status.abbr(), theme.ns),
                 };
                 cells.push(c);
             }
-            if should_display {
-                table.add_row(Row::new(cells));
+            // Keep our own row visible, showing where we actually are
+            if row.pinned {
+                cells[0] = match row.rank {
+                    Some(rank) => plain(&format!("#{} {}", rank, name)),
+                    None => plain(&format!("- {}", name)),
+                };
             }
+            table.add_row(Row::new(cells));
+        }
+
+        if let Some(stats) = stats.as_ref().filter(|_| meta.show_solve_stats()) {
+            let mut cells = vec![cell!(c->"Avg AC")];
+            for col in columns.iter() {
+                let text = match col {
+                    Column::Problem(pid) => stats
+                        .get(pid)
+                        .and_then(|st| st.average)
+                        .map(format_elapsed)
+                        .unwrap_or_else(|| "-".to_owned()),
+                    _ => String::new(),
+                };
+                cells.push(Cell::new_align(&text, Alignment::CENTER));
+            }
+            table.add_row(Row::new(cells));
+        }
+        if let Some(stats) = stats.as_ref().filter(|_| meta.show_solve_ratio()) {
+            let mut cells = vec![cell!(c->"AC Rate")];
+            for col in columns.iter() {
+                let text = match col {
+                    Column::Problem(pid) => match stats.get(pid).and_then(|st| st.solve_ratio()) {
+                        Some(ratio) => format!("{:.0}%", ratio * 100.0),
+                        None => "—".to_owned(),
+                    },
+                    _ => String::new(),
+                };
+                cells.push(Cell::new_align(&text, Alignment::CENTER));
+            }
+            table.add_row(Row::new(cells));
         }
 
         // Also generate one at footer
-        table.add_row(Row::new(prob_cells.clone()));
+        if meta.show_footer_header(shown) {
+            table.add_row(Row::new(prob_cells));
+        }
+
+        table
+    }
+
+    /// The latest solves and wrong answers, newest first
+    pub fn feed_lines(&self, meta: &Metadata) -> Vec<String> {
+        let user_lock = lock(&self.user_map);
+        let feed_lock = lock(&self.feed);
+        let shared = shared_names(meta, &user_lock);
+        let tz = meta.timezone().unwrap_or(DisplayTz::Local);
+        let freeze = meta.freeze_time().unwrap_or(None);
+        feed_lock
+            .iter()
+            .rev()
+            .filter(|ev| !meta.exclude_users().contains(&ev.user_id))
+            // Its verdict would give away what the frozen board hides
+            .filter(|ev| !matches!(freeze, Some(at) if ev.time >= at))
+            .take(meta.feed_length())
+            .map(|ev| {
+                let name = user_lock
+                    .get(&ev.user_id)
+                    .map(|user| user.name.as_str())
+                    .unwrap_or_default();
+                let action = match ev.status {
+                    SolveStatus::Accepted => "solved",
+                    _ => "WA on",
+                };
+                format!(
+                    "{} {} {} {}",
+                    tz.format(&ev.time, "%m-%d %H:%M"),
+                    display_name(meta, &shared, ev.user_id, name),
+                    action,
+                    meta.problem_label(ev.problem_id)
+                )
+            })
+            .collect()
+    }
+
+    /// Solve count and time-to-AC per problem, measured from `contest_start`
+    ///
+    /// Without a start time the earliest AC on the board is used instead.
+    pub fn problem_stats(&self, meta: &Metadata) -> BTreeMap<u32, ProblemStats> {
+        let start = meta.contest_start().unwrap_or(None);
+        let rounding = meta.minute_rounding();
+        let user_lock = lock(&self.user_map);
+        let user_map = visible_users(meta, &user_lock);
+        let mut stats: BTreeMap<u32, ProblemStats> = BTreeMap::new();
+        for (&pid, p) in counted_users(meta, &user_map).flat_map(|(_, user)| user.problems.iter()) {
+            if matches!(p.status, SolveStatus::Accepted | SolveStatus::WrongAnswer) {
+                stats.entry(pid).or_default().attempted += 1;
+            }
+        }
+
+        let solves = || {
+            counted_users(meta, &user_map).flat_map(|(&uid, user)| {
+                user.problems
+                    .iter()
+                    .filter(|(_, p)| p.status == SolveStatus::Accepted)
+                    .filter_map(move |(&pid, p)| p.solved_at.map(|t| (pid, uid, t)))
+            })
+        };
+        let start = match start.or_else(|| solves().map(|(_, _, t)| t).min()) {
+            Some(start) => start,
+            None => return stats,
+        };
+
+        let mut totals: BTreeMap<u32, Duration> = BTreeMap::new();
+        for (pid, uid, t) in solves() {
+            let elapsed = Duration::minutes(rounding.minutes(t - start));
+            let entry = stats.entry(pid).or_default();
+            entry.solves += 1;
+            if !matches!(entry.fastest, Some((_, best)) if best <= elapsed) {
+                entry.fastest = Some((uid, elapsed));
+            }
+            let total = totals.entry(pid).or_insert_with(Duration::zero);
+            *total = *total + elapsed;
+        }
+        for (pid, total) in totals {
+            let entry = stats.get_mut(&pid).unwrap();
+            entry.average = Some(total / entry.solves as i32);
+        }
+        stats
+    }
+
+    /// The displayed problems every displayed user solved, and those nobody did
+    ///
+    /// Displayed means what `gen_table` shows, after `min_ac` and `top_n`.
+    pub fn coverage_summary(&self, meta: &Metadata) -> CoverageSummary {
+        let user_lock = lock(&self.user_map);
+        let user_map = visible_users(meta, &user_lock);
+        let problems_lock = lock(&self.problem_set);
+        let info_lock = lock(&self.problem_info);
+        let prob_list = Self::problem_list(meta, &problems_lock, &info_lock);
+        let self_id = *read(&self.self_id);
+        let shown: Vec<&UserRecord> = Self::board_rows(
+            meta,
+            &user_map,
+            &prob_list,
+            self_id,
+            &TableOptions::default(),
+        )
+        .into_iter()
+        .map(|row| row.user)
+        .collect();
+
+        let solvers = |pid: &u32| {
+            shown
+                .iter()
+                .filter(|user| {
+                    user.problems
+                        .get(pid)
+                        .is_some_and(|p| p.status == SolveStatus::Accepted)
+                })
+                .count()
+        };
+        let mut summary = CoverageSummary {
+            users: shown.len(),
+            ..CoverageSummary::default()
+        };
+        for pid in &prob_list {
+            match solvers(pid) {
+                0 => summary.solved_by_none.insert(*pid),
+                n if n == shown.len() => summary.solved_by_all.insert(*pid),
+                _ => false,
+            };
+        }
+        summary
+    }
+
+    /// Every problem of one user with its status, attempts and solve time
+    pub fn export_user_report(
+        &self,
+        uid: u32,
+        format: ReportFormat,
+        meta: &Metadata,
+    ) -> SimpleResult<String> {
+        let user_lock = lock(&self.user_map);
+        let user_map = visible_users(meta, &user_lock);
+        let info_lock = lock(&self.problem_info);
+        let user = user_map.get(&uid).ok_or_else(|| SimpleError::Custom {
+            message: format!("No submissions from user {}", uid),
+        })?;
+        let tz = meta.timezone().unwrap_or(DisplayTz::Local);
+        let entries: Vec<ReportEntry> = user
+            .problems
+            .iter()
+            .map(|(&pid, p)| ReportEntry {
+                problem_id: meta.problem_id(pid),
+                group_id: meta.problem_group(pid),
+                label: meta.problem_label(pid),
+                title: info_lock
+                    .get(&pid)
+                    .map(|info| info.title.clone())
+                    .unwrap_or_default(),
+                status: format!("{:#}", p.status),
+                attempts: p.attempts,
+                // Hidden along with the verdicts since the freeze
+                wrong_answers: if p.status == SolveStatus::Frozen {
+                    0
+                } else {
+                    p.wa_count
+                },
+                solved_at: p.solved_at.map(|t| tz.format(&t, "%Y-%m-%d %H:%M:%S")),
+            })
+            .collect();
+        let name = meta.name_display().show(uid, &user.name);
+
+        Ok(match format {
+            ReportFormat::Json => serde_json::to_string_pretty(&UserReport {
+                user_id: uid,
+                name,
+                problems: entries,
+            })?,
+            ReportFormat::Csv => {
+                let mut out =
+                    String::from("problem_id,title,status,attempts,wrong_answers,solved_at\n");
+                for e in &entries {
+                    out.push_str(&format!(
+                        "{},{},{},{},{},{}\n",
+                        e.label,
+                        csv_field(&e.title),
+                        e.status,
+                        e.attempts,
+                        e.wrong_answers,
+                        e.solved_at.as_deref().unwrap_or("")
+                    ));
+                }
+                out
+            }
+            ReportFormat::Markdown => {
+                let mut out = format!("# {}\n\n", name);
+                out.push_str("| Problem | Title | Status | Attempts | WA | Solved At |\n");
+                out.push_str("|---|---|---|---|---|---|\n");
+                for e in &entries {
+                    out.push_str(&format!(
+                        "| {} | {} | {} | {} | {} | {} |\n",
+                        e.label,
+                        e.title.replace('|', "\\|"),
+                        e.status,
+                        e.attempts,
+                        e.wrong_answers,
+                        e.solved_at.as_deref().unwrap_or("")
+                    ));
+                }
+                out
+            }
+        })
+    }
+
+    /// The rows of `export_csv` with pseudonyms and no user ids, safe to
+    /// share publicly
+    pub fn export_public(&self, format: ReportFormat, meta: &Metadata) -> SimpleResult<String> {
+        let pseudonym = meta.pseudonyms()?;
+        let user_lock = lock(&self.user_map);
+        let user_map = visible_users(meta, &user_lock);
+        let problems_lock = lock(&self.problem_set);
+        let info_lock = lock(&self.problem_info);
+        let prob_list = Self::problem_list(meta, &problems_lock, &info_lock);
+        let self_id = *read(&self.self_id);
+        let rows: Vec<PublicRow> = Self::board_rows(
+            meta,
+            &user_map,
+            &prob_list,
+            self_id,
+            &TableOptions::default(),
+        )
+        .into_iter()
+        .map(|row| PublicRow {
+            rank: row.rank,
+            name: pseudonym(row.user_id),
+            solved: row.user.ac_count(&prob_list),
+            problems: prob_list
+                .iter()
+                .map(|pid| {
+                    let cell = row.user.problems.get(pid).copied().unwrap_or_default();
+                    PublicCell {
+                        problem_id: meta.problem_id(*pid),
+                        group_id: meta.problem_group(*pid),
+                        status: cell.status.abbr().to_owned(),
+                        attempts: cell.attempts,
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+
+        Ok(match format {
+            ReportFormat::Json => serde_json::to_string_pretty(&rows)?,
+            ReportFormat::Csv => {
+                let mut out = String::from("rank,name,solved");
+                for pid in &prob_list {
+                    out.push_str(&format!(",{}", meta.problem_label(*pid)));
+                }
+                out.push('\n');
+                for row in &rows {
+                    out.push_str(&format!(
+                        "{},{},{}",
+                        row.rank.map(|r| r.to_string()).unwrap_or_default(),
+                        csv_field(&row.name),
+                        row.solved
+                    ));
+                    for p in &row.problems {
+                        out.push_str(&format!(",{}/{}", p.status, p.attempts));
+                    }
+                    out.push('\n');
+                }
+                out
+            }
+            ReportFormat::Markdown => {
+                let mut out = String::from("| Rank | Name | Solved |");
+                let mut rule = String::from("|---|---|---|");
+                for pid in &prob_list {
+                    out.push_str(&format!(" {} |", meta.problem_label(*pid)));
+                    rule.push_str("---|");
+                }
+                out.push('\n');
+                out.push_str(&rule);
+                out.push('\n');
+                for row in &rows {
+                    out.push_str(&format!(
+                        "| {} | {} | {} |",
+                        row.rank.map(|r| r.to_string()).unwrap_or_default(),
+                        row.name,
+                        row.solved
+                    ));
+                    for p in &row.problems {
+                        out.push_str(&format!(" {}/{} |", p.status, p.attempts));
+                    }
+                    out.push('\n');
+                }
+                out
+            }
+        })
+    }
+
+    /// The rows of `gen_table` as CSV, with the status and wrong tries of
+    /// each shown problem
+    ///
+    /// Rank is empty for those below `min_ac`, penalty without `contest_start`.
+    pub fn export_csv(&self, meta: &Metadata, opts: &TableOptions) -> String {
+        let (problems, rows) = self.export_rows(meta, opts);
+        let mut out = String::from("rank,user_id,name");
+        for pid in &problems {
+            let label = meta.problem_label(*pid);
+            out.push_str(&format!(",{},{}_wa", label, label));
+        }
+        out.push_str(",solved,penalty\n");
+        let or_empty = |v: Option<String>| v.unwrap_or_default();
+        for row in rows {
+            out.push_str(&format!(
+                "{},{},{}",
+                or_empty(row.rank.map(|r| r.to_string())),
+                row.user_id,
+                csv_field(&row.name)
+            ));
+            for p in &row.problems {
+                out.push_str(&format!(",{},{}", p.status, p.wa_count));
+            }
+            out.push_str(&format!(
+                ",{},{}\n",
+                row.solved,
+                or_empty(row.penalty.map(|p| p.to_string()))
+            ));
+        }
+        out
+    }
+
+    /// The rows of `export_csv` as a JSON array, in the same order
+    pub fn export_json(&self, meta: &Metadata, opts: &TableOptions) -> SimpleResult<String> {
+        Ok(serde_json::to_string_pretty(
+            &self.export_rows(meta, opts).1,
+        )?)
+    }
+
+    /// The shown problems, and the rows of `gen_table` with their names as shown
+    fn export_rows(&self, meta: &Metadata, opts: &TableOptions) -> (Vec<u32>, Vec<ExportRow>) {
+        let user_lock = lock(&self.user_map);
+        let user_map = visible_users(meta, &user_lock);
+        let problems_lock = lock(&self.problem_set);
+        let info_lock = lock(&self.problem_info);
+        let prob_list = Self::problem_list(meta, &problems_lock, &info_lock);
+        let shared = shared_names(meta, &user_map);
+        let self_id = *read(&self.self_id);
+        let start = meta.contest_start().unwrap_or(None);
+        let rows = Self::board_rows(meta, &user_map, &prob_list, self_id, opts)
+            .into_iter()
+            .map(|row| ExportRow {
+                rank: row.rank,
+                user_id: row.user_id,
+                name: display_name(meta, &shared, row.user_id, &row.user.name),
+                problems: prob_list
+                    .iter()
+                    .map(|pid| {
+                        let cell = row.user.problems.get(pid).copied().unwrap_or_default();
+                        // Like the board, whether the tries since the freeze failed stays hidden
+                        let frozen = cell.status == SolveStatus::Frozen;
+                        ExportCell {
+                            problem_id: meta.problem_id(*pid),
+                            group_id: meta.problem_group(*pid),
+                            status: cell.status.export_abbr(),
+                            wa_count: if frozen { 0 } else { cell.wa_count },
+                        }
+                    })
+                    .collect(),
+                solved: row.user.ac_count(&prob_list),
+                penalty: start.map(|start| {
+                    let (rounding, policy) = (meta.minute_rounding(), meta.penalty_policy());
+                    let minutes = row.user.penalty(&prob_list, start, rounding, policy);
+                    match meta.penalty_format() {
+                        PenaltyFormat::Minutes => ExportPenalty::Minutes(minutes),
+                        format => ExportPenalty::Text(format.show(minutes)),
+                    }
+                }),
+            })
+            .collect();
+        (prob_list, rows)
+    }
+
+    /// `user_id,name,pseudonym` lines for the rows of `export_public`, kept by the instructor
+    pub fn pseudonym_map(&self, meta: &Metadata) -> SimpleResult<String> {
+        let pseudonym = meta.pseudonyms()?;
+        let user_lock = lock(&self.user_map);
+        let user_map = visible_users(meta, &user_lock);
+        let problems_lock = lock(&self.problem_set);
+        let info_lock = lock(&self.problem_info);
+        let prob_list = Self::problem_list(meta, &problems_lock, &info_lock);
+        let self_id = *read(&self.self_id);
+        let rows = Self::board_rows(
+            meta,
+            &user_map,
+            &prob_list,
+            self_id,
+            &TableOptions::default(),
+        );
+        let mut out = String::from("user_id,name,pseudonym\n");
+        for row in rows {
+            out.push_str(&format!(
+                "{},{},{}\n",
+                row.user_id,
+                csv_field(&row.user.name),
+                csv_field(&pseudonym(row.user_id))
+            ));
+        }
+        Ok(out)
+    }
+
+    /// A single-column board for one problem, solvers first in solve order
+    pub fn gen_problem_table(&self, pid: u32, meta: &Metadata) -> Table {
+        let mut table = Table::new();
+        let user_lock = lock(&self.user_map);
+        let user_map = visible_users(meta, &user_lock);
+        let shared = shared_names(meta, &user_map);
+        let theme = meta.theme().unwrap_or_default();
+        let tz = meta.timezone().unwrap_or(DisplayTz::Local);
+
+        let entries = problem_entries(meta, &user_map, pid);
+
+        table.add_row(Row::new(vec![cell!(""), cell!(c->meta.problem_label(pid))]));
+        let t = self.cache_time();
+        table.add_row(Row::new(vec![
+            cell!(c->"Updated At"),
+            Cell::new_align(
+                tz.format(&t, "%Y-%m-%d\n%H:%M:%S").as_str(),
+                Alignment::CENTER,
+            ),
+        ]));
+
+        for (uid, user, p) in entries {
+            let c = match p.status {
+                SolveStatus::Accepted => {
+                    let mut text = format!("{} / {}", p.status, p.tries(meta.try_count()));
+                    if let Some(at) = p.solved_at {
+                        text.push('\n');
+                        text.push_str(&tz.format(&at, "%H:%M:%S"));
+                    }
+                    colored_cell(&text, theme.ac)
+                }
+                SolveStatus::WrongAnswer => colored_cell(
+                    &format!(
+                        "{} / {}",
+                        p.status_text(meta.show_verdicts()),
+                        p.tries(meta.try_count())
+                    ),
+                    theme.wa,
+                ),
+                SolveStatus::Frozen => colored_cell(p.status.abbr(), theme.pending),
+                _ => colored_cell(p.status.abbr(), theme.attempted),
+            };
+            let name = display_name(meta, &shared, uid, &user.name);
+            table.add_row(Row::new(vec![cell!(c->name), c]));
+        }
+        table
+    }
+
+    /// The user on line `row` of `gen_table`, none on the other lines
+    pub fn user_at_row(&self, meta: &Metadata, opts: &TableOptions, row: usize) -> Option<u32> {
+        let user_lock = lock(&self.user_map);
+        let user_map = visible_users(meta, &user_lock);
+        let problems_lock = lock(&self.problem_set);
+        let info_lock = lock(&self.problem_info);
+        let prob_list = Self::problem_list(meta, &problems_lock, &info_lock);
+        let self_id = *read(&self.self_id);
+        let rows = Self::board_rows(meta, &user_map, &prob_list, self_id, opts);
+        // Below the problem groups if any, the problems and the update time
+        let header = if meta.problem_groups().is_empty() {
+            2
+        } else {
+            3
+        };
+        rows.get(row.checked_sub(header)?).map(|row| row.user_id)
+    }
+
+    /// The user on line `row` of `gen_problem_table`
+    pub fn problem_user_at_row(&self, pid: u32, meta: &Metadata, row: usize) -> Option<u32> {
+        let user_lock = lock(&self.user_map);
+        let user_map = visible_users(meta, &user_lock);
+        let entries = problem_entries(meta, &user_map, pid);
+        // Below the problem and the update time
+        entries.get(row.checked_sub(2)?).map(|&(uid, _, _)| uid)
+    }
+
+    /// Resolve what was typed in the compare prompt, either a name or a user id
+    pub fn find_user(&self, query: &str) -> SimpleResult<u32> {
+        let user_lock = lock(&self.user_map);
+        let query = query.trim();
+        if let Ok(uid) = query.parse::<u32>() {
+            if user_lock.contains_key(&uid) {
+                return Ok(uid);
+            }
+        }
+        // A name as the board shows it when several users share it
+        if let Some((_, id)) = query.rsplit_once(" #") {
+            if let Ok(uid) = id.parse::<u32>() {
+                if user_lock.contains_key(&uid) {
+                    return Ok(uid);
+                }
+            }
+        }
+        let mut found: Vec<u32> = user_lock
+            .iter()
+            .filter(|(_, user)| user.name.eq_ignore_ascii_case(query))
+            .map(|(&uid, _)| uid)
+            .collect();
+        // Fall back to partial names when nobody is called exactly that
+        if found.is_empty() && !query.is_empty() {
+            let query = query.to_lowercase();
+            found = user_lock
+                .iter()
+                .filter(|(_, user)| user.name.to_lowercase().contains(&query))
+                .map(|(&uid, _)| uid)
+                .collect();
+        }
+        match found.as_slice() {
+            [] => Err(SimpleError::Custom {
+                message: format!("No user matches \"{}\"", query),
+            }),
+            [uid] => Ok(*uid),
+            _ => {
+                let ids: Vec<String> = found.iter().map(|uid| uid.to_string()).collect();
+                Err(SimpleError::Custom {
+                    message: format!(
+                        "\"{}\" matches several users, enter one of the ids instead: {}",
+                        query,
+                        ids.join(", ")
+                    ),
+                })
+            }
+        }
+    }
+
+    /// Two users side by side, highlighting problems only one of them solved
+    pub fn gen_compare_table(&self, a: u32, b: u32, meta: &Metadata) -> Table {
+        let mut table = Table::new();
+        let user_lock = lock(&self.user_map);
+        let user_map = visible_users(meta, &user_lock);
+        let theme = meta.theme().unwrap_or_default();
+        let empty = UserRecord::default();
+        let user_a = user_map.get(&a).unwrap_or(&empty);
+        let user_b = user_map.get(&b).unwrap_or(&empty);
+        let shared = shared_names(meta, &user_map);
+
+        let prob_list: BTreeSet<u32> = user_a
+            .problems
+            .keys()
+            .chain(user_b.problems.keys())
+            .filter(|&&pid| !meta.exclude_problems().contains(&meta.problem_id(pid)))
+            .copied()
+            .collect();
+
+        table.set_titles(Row::new(vec![
+            cell!(c->"Problem"),
+            cell!(c->display_name(meta, &shared, a, &user_a.name)),
+            cell!(c->display_name(meta, &shared, b, &user_b.name)),
+        ]));
+        let text = |p: &ProblemCell| match p.status {
+            SolveStatus::Accepted => format!("{} / {}", p.status, p.tries(meta.try_count())),
+            SolveStatus::WrongAnswer => format!(
+                "{} / {}",
+                p.status_text(meta.show_verdicts()),
+                p.tries(meta.try_count())
+            ),
+            _ => p.status.to_string(),
+        };
+        for &pid in &prob_list {
+            let pa = user_a.problems.get(&pid).copied().unwrap_or_default();
+            let pb = user_b.problems.get(&pid).copied().unwrap_or_default();
+            let solved_a = pa.status == SolveStatus::Accepted;
+            let solved_b = pb.status == SolveStatus::Accepted;
+            let cell = |p: &ProblemCell, solved: bool| {
+                if solved_a != solved_b {
+                    colored_cell(&text(p), if solved { theme.ac } else { theme.wa })
+                } else {
+                    cell!(c->text(p))
+                }
+            };
+            table.add_row(Row::new(vec![
+                cell!(c->meta.problem_label(pid)),
+                cell(&pa, solved_a),
+                cell(&pb, solved_b),
+            ]));
+        }
+        table.add_row(Row::new(vec![
+            cell!(c->"Solved"),
+            cell!(c->user_a.solved_among(prob_list.iter())),
+            cell!(c->user_b.solved_among(prob_list.iter())),
+        ]));
+        table
+    }
+}
+
+/// How fast and how often a problem was solved, see `Scoreboard::problem_stats`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProblemStats {
+    pub solves: usize,
+    /// Users with a judged submission, whether or not they solved it
+    pub attempted: usize,
+    pub average: Option<Duration>,
+    /// The user id and time of the quickest AC
+    pub fastest: Option<(u32, Duration)>,
+}
+
+/// See `Scoreboard::coverage_summary`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CoverageSummary {
+    /// How many users it was computed over
+    pub users: usize,
+    pub solved_by_all: BTreeSet<u32>,
+    pub solved_by_none: BTreeSet<u32>,
+}
+
+impl ProblemStats {
+    /// The share of `attempted` who solved it, `None` if nobody tried
+    pub fn solve_ratio(&self) -> Option<f64> {
+        if self.attempted == 0 {
+            None
+        } else {
+            Some(self.solves as f64 / self.attempted as f64)
+        }
+    }
+}
+
+/// The records as the board shows them, frozen when `freeze_time` is set
+fn visible_users<'a>(
+    meta: &Metadata,
+    users: &'a BTreeMap<u32, UserRecord>,
+) -> Cow<'a, BTreeMap<u32, UserRecord>> {
+    match meta.freeze_time().unwrap_or(None) {
+        Some(freeze) => Cow::Owned(
+            users
+                .iter()
+                .map(|(&uid, user)| (uid, user.frozen_at(freeze)))
+                .collect(),
+        ),
+        None => Cow::Borrowed(users),
+    }
+}
+
+/// The users the board is computed over, without `exclude_users`
+fn counted_users<'a>(
+    meta: &'a Metadata,
+    users: &'a BTreeMap<u32, UserRecord>,
+) -> impl Iterator<Item = (&'a u32, &'a UserRecord)> {
+    users
+        .iter()
+        .filter(move |(uid, _)| !meta.exclude_users().contains(uid))
+}
+
+/// The rows of `gen_problem_table`, solvers first in solve order
+fn problem_entries<'a>(
+    meta: &'a Metadata,
+    users: &'a BTreeMap<u32, UserRecord>,
+    pid: u32,
+) -> Vec<(u32, &'a UserRecord, ProblemCell)> {
+    let mut entries: Vec<(u32, &UserRecord, ProblemCell)> = counted_users(meta, users)
+        .filter_map(|(&uid, user)| user.problems.get(&pid).map(|p| (uid, user, *p)))
+        .filter(|(_, _, p)| p.status != SolveStatus::None)
+        .collect();
+    entries.sort_by_key(|(_, _, p)| (p.solved_at.is_none(), p.solved_at));
+    entries
+}
+
+/// Who gets the star on each problem, among the counted `users`
+///
+/// Only the cells are compared, so excluded or frozen solves never take it.
+/// A tie within a second goes to the one `recorded` by `save_submissions`,
+/// then to the lower user id.
+fn first_solvers(
+    users: &[(&u32, &UserRecord)],
+    recorded: &BTreeMap<u32, FirstSolve>,
+) -> BTreeMap<u32, u32> {
+    let mut first: BTreeMap<u32, (DateTime<Local>, bool, u32)> = BTreeMap::new();
+    for &(&uid, user) in users {
+        for (&pid, cell) in &user.problems {
+            let time = match cell.solved_at {
+                Some(time) if cell.status == SolveStatus::Accepted => time,
+                _ => continue,
+            };
+            let unrecorded = !matches!(recorded.get(&pid), Some(f) if f.user_id == uid);
+            let key = (time, unrecorded, uid);
+            match first.get(&pid) {
+                Some(known) if *known <= key => {}
+                _ => {
+                    first.insert(pid, key);
+                }
+            }
+        }
+    }
+    first
+        .into_iter()
+        .map(|(pid, (_, _, uid))| (pid, uid))
+        .collect()
+}
+
+/// The names `name_display` shows for more than one user
+fn shared_names(meta: &Metadata, users: &BTreeMap<u32, UserRecord>) -> BTreeSet<String> {
+    let mut seen = BTreeSet::new();
+    let mut shared = BTreeSet::new();
+    for (&uid, user) in users {
+        // Not looked up yet, rather than actually the same
+        if user.name.is_empty() {
+            continue;
+        }
+        let name = meta.name_display().show(uid, &user.name);
+        if !seen.insert(name.clone()) {
+            shared.insert(name);
+        }
+    }
+    shared
+}
+
+/// `name_display` applied, with ` #id` added when the name is in `shared`
+fn display_name(meta: &Metadata, shared: &BTreeSet<String>, uid: u32, name: &str) -> String {
+    let name = meta.name_display().show(uid, name);
+    if shared.contains(&name) {
+        format!("{} #{}", name, uid)
+    } else {
+        name
+    }
+}
+
+/// `H:MM`, hours may go past a day
+fn format_elapsed(d: Duration) -> String {
+    format!("{}:{:02}", d.num_hours(), d.num_minutes() % 60)
+}
+
+/// A user row of the board, see `Scoreboard::board_rows`
+struct BoardRow<'a> {
+    user_id: u32,
+    user: &'a UserRecord,
+    /// None when below `min_ac`, unless `global_rank` is set
+    rank: Option<usize>,
+    /// Only shown as our own row with `pin_self`, past `top_n` or below `min_ac`
+    pinned: bool,
+}
+
+/// How `Scoreboard::gen_table` narrows and orders the rows, the whole board by default
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TableOptions<'a> {
+    /// Move the solvers of this problem up, fastest first
+    pub focus: Option<u32>,
+    /// Keep only the rows whose status on a problem matches, ranks still count everyone
+    pub filter: Option<(u32, StatusFilter)>,
+    /// Keep only the users whose shown name contains this, ignoring case
+    pub search: Option<&'a str>,
+}
+
+/// Rows kept by `TableOptions::filter`, by their status on one problem
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusFilter {
+    /// Anything but AC
+    Unsolved,
+    Is(SolveStatus),
+}
+
+impl StatusFilter {
+    pub const ALL: &'static [StatusFilter] = &[
+        StatusFilter::Unsolved,
+        StatusFilter::Is(SolveStatus::None),
+        StatusFilter::Is(SolveStatus::WrongAnswer),
+        StatusFilter::Is(SolveStatus::Accepted),
+    ];
+
+    pub fn matches(self, status: SolveStatus) -> bool {
+        match self {
+            StatusFilter::Unsolved => status != SolveStatus::Accepted,
+            StatusFilter::Is(s) => s == status,
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            StatusFilter::Unsolved => "Not solved".to_owned(),
+            StatusFilter::Is(s) => format!("{:#}", s),
+        }
+    }
+}
+
+/// Formats of `Scoreboard::export_user_report`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Csv,
+    Json,
+    Markdown,
+}
+
+impl ReportFormat {
+    pub fn parse(s: &str) -> SimpleResult<Self> {
+        match s {
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            "md" | "markdown" => Ok(ReportFormat::Markdown),
+            _ => Err(SimpleError::Custom {
+                message: format!("Unknown report format: {}", s),
+            }),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PublicRow {
+    rank: Option<usize>,
+    name: String,
+    solved: usize,
+    problems: Vec<PublicCell>,
+}
+
+#[derive(Serialize)]
+struct PublicCell {
+    problem_id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group_id: Option<u32>,
+    status: String,
+    attempts: usize,
+}
+
+#[derive(Serialize)]
+struct ExportRow {
+    rank: Option<usize>,
+    user_id: u32,
+    name: String,
+    problems: Vec<ExportCell>,
+    solved: usize,
+    penalty: Option<ExportPenalty>,
+}
+
+/// A number in JSON unless `penalty_format` asks for text
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+enum ExportPenalty {
+    Minutes(i64),
+    Text(String),
+}
+
+impl fmt::Display for ExportPenalty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExportPenalty::Minutes(minutes) => write!(f, "{}", minutes),
+            ExportPenalty::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExportCell {
+    problem_id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group_id: Option<u32>,
+    status: &'static str,
+    wa_count: usize,
+}
+
+#[derive(Serialize)]
+struct UserReport {
+    user_id: u32,
+    name: String,
+    problems: Vec<ReportEntry>,
+}
+
+#[derive(Serialize)]
+struct ReportEntry {
+    problem_id: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group_id: Option<u32>,
+    /// The column header, only used by the CSV and markdown reports
+    #[serde(skip)]
+    label: String,
+    title: String,
+    status: String,
+    attempts: usize,
+    wrong_answers: usize,
+    solved_at: Option<String>,
+}
+
+/// Quote a CSV field when it holds a separator, quote or newline
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Decode `bytes` as a whole, so a cache in another layout isn't taken for this one
+fn decode_exact<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> SimpleResult<T> {
+    let mut rest = bytes;
+    // A misread length would otherwise be allocated before failing
+    let value = bincode::config()
+        .limit(bytes.len() as u64)
+        .deserialize_from(&mut rest)?;
+    if !rest.is_empty() {
+        return Err(SimpleError::Custom {
+            message: format!("{} bytes left over after the cache", rest.len()),
+        });
+    }
+    Ok(value)
+}
+
+/// The first cache layout, users of wrong tries and status per problem
+#[derive(Deserialize)]
+struct FirstCache {
+    user_map: BTreeMap<u32, FirstUser>,
+    problem_set: BTreeSet<u32>,
+    cache_time: DateTime<Local>,
+}
+
+#[derive(Deserialize)]
+struct FirstUser {
+    id: u32,
+    name: String,
+    problems: BTreeMap<u32, FirstCell>,
+}
+
+#[derive(Deserialize)]
+struct FirstCell {
+    wa_count: usize,
+    status: SolveStatus,
+}
+
+impl From<FirstCache> for Scoreboard {
+    fn from(old: FirstCache) -> Self {
+        let users = old
+            .user_map
+            .into_iter()
+            .map(|(uid, user)| (uid, user.into()))
+            .collect();
+        let board = Scoreboard::new();
+        *lock(&board.user_map) = users;
+        *lock(&board.problem_set) = old.problem_set;
+        *write(&board.cache_time) = old.cache_time;
+        board
+    }
+}
+
+impl From<FirstUser> for UserRecord {
+    fn from(old: FirstUser) -> Self {
+        let problems = old
+            .problems
+            .into_iter()
+            .map(|(pid, cell)| (pid, cell.into()))
+            .collect();
+        UserRecord {
+            id: old.id,
+            name: old.name,
+            problems,
+            ..UserRecord::default()
+        }
+    }
+}
+
+impl From<FirstCell> for ProblemCell {
+    fn from(old: FirstCell) -> Self {
+        // Repeated ACs were not kept, so only the first counts as a try
+        let attempts = match old.status {
+            SolveStatus::Accepted => old.wa_count + 1,
+            _ => old.wa_count,
+        };
+        ProblemCell {
+            wa_count: old.wa_count,
+            status: old.status,
+            attempts,
+            ..ProblemCell::default()
+        }
+    }
+}
+
+/// Where a problem was seen during sync, see `Scoreboard::problem_origin`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProblemOrigin {
+    /// Only in the group's problem list
+    List,
+    /// Only in submissions, the problem has left the group
+    Submissions,
+    Both,
+}
+
+/// A column of the table next to the user names
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Column {
+    Problem(u32),
+    /// AC count within the problem group at this index
+    Subtotal(usize),
+    Total,
+    /// Each user's sum of best scores, see `UserRecord::score`
+    Score,
+    /// Each user's total penalty in minutes, see `UserRecord::penalty`
+    Penalty,
+    /// The time of each user's latest submission, whatever the problem
+    LastSubmit,
+}
+
+fn layout_columns(prob_list: &[u32], groups: &[ProblemGroup]) -> Vec<Column> {
+    if groups.is_empty() {
+        return prob_list.iter().map(|&pid| Column::Problem(pid)).collect();
+    }
+    let mut columns = Vec::new();
+    for (i, group) in groups.iter().enumerate() {
+        for &pid in group.problems.iter().filter(|pid| prob_list.contains(pid)) {
+            columns.push(Column::Problem(pid));
+        }
+        columns.push(Column::Subtotal(i));
+    }
+    for &pid in prob_list {
+        if !groups.iter().any(|g| g.problems.contains(&pid)) {
+            columns.push(Column::Problem(pid));
+        }
+    }
+    columns.push(Column::Total);
+    columns
+}
+
+/// The color of a partial score, stepping from the wrong answer color up to
+/// the accepted one as a percentage nears the full score
+fn score_color(theme: &BoardTheme, meta: &Metadata, pid: u32, score: i32) -> color::Color {
+    match meta.score_display() {
+        ScoreDisplay::Absolute => theme.partial,
+        ScoreDisplay::Percent => match meta.scaled_score(pid, score) {
+            percent if percent >= 100 => theme.ac,
+            percent if percent >= 50 => theme.partial,
+            _ => theme.wa,
+        },
+    }
+}
+
+fn colored_cell(text: &str, fg: color::Color) -> Cell {
+    Cell::new_align(text, Alignment::CENTER).with_style(Attr::ForegroundColor(fg))
+}
+
+/// Reverse the cell at `(row, col)`, moved inside the table when past its end
+///
+/// Rows with spanning cells have fewer of them, those pick their last cell
+/// while the column returned stays where it was for the rows below.
+pub fn highlight_cell(table: &mut Table, (row, col): (usize, usize)) -> (usize, usize) {
+    let width = table.row_iter().map(Row::len).max().unwrap_or(0);
+    let row = row.min(table.len().saturating_sub(1));
+    let col = col.min(width.saturating_sub(1));
+    if let Some(cells) = table.get_mut_row(row) {
+        let last = cells.len().saturating_sub(1);
+        if let Some(cell) = cells.get_mut_cell(col.min(last)) {
+            cell.style(Attr::Standout(true));
+        }
+    }
+    (row, col)
+}
+
+// A panic while holding a lock shouldn't take the whole board down with it,
+// so carry on with whatever state the lock was left in
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn read<T>(l: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    l.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write<T>(l: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    l.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// `-` stands for stdin when loading and stdout when saving
+pub fn is_std_stream(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Next to `path` so the rename stays on one filesystem, unique per save
+/// as the autosave may run alongside a refresh
+fn temp_path(path: &Path) -> PathBuf {
+    static SAVES: AtomicUsize = AtomicUsize::new(0);
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(format!(
+        ".tmp{}-{}",
+        std::process::id(),
+        SAVES.fetch_add(1, AtomicOrdering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+/// Everything `sync` needs to know about what to fetch and how to count it
+#[derive(Clone, Debug)]
+pub struct SyncConfig {
+    pub gids: Vec<u32>,
+    pub contest: Option<u32>,
+    /// Only fetch this problem's submissions
+    pub problem: Option<u32>,
+    pub overlap: Duration,
+    pub accepted: Vec<u32>,
+    /// Verdict ids counted as wrong tries
+    pub rejected: Vec<u32>,
+    pub window: TimeWindow,
+    /// Keeps each group's problems in columns of their own
+    pub columns: Option<ProblemColumns>,
+}
+
+impl SyncConfig {
+    pub fn new(meta: &Metadata, problem: Option<u32>) -> Self {
+        Self {
+            gids: meta.get_groups(),
+            contest: meta.contest(),
+            problem,
+            overlap: meta.overlap(),
+            accepted: meta.accepted_verdicts(),
+            rejected: meta.rejected_verdicts(),
+            window: meta.window().unwrap_or_default(),
+            columns: meta.problem_columns(),
+        }
+    }
+}
+
+pub fn sync(
+    board: Arc<Scoreboard>,
+    foj: Arc<FojApi>,
+    config: SyncConfig,
+) -> impl Future<Item = (), Error = SimpleError> + 'static {
+    let gids_prob = config.gids.clone();
+    let columns_prob = config.columns.clone();
+    let board_sess = board.clone();
+    let board_prob = board.clone();
+    let board_arc = board.clone();
+    let foj_err = foj.clone();
+    foj.session()
+        .map(move |session| {
+            info!("Authentication Succuss!");
+            trace!("{:?}", session);
+            *write(&board_sess.self_id) = Some(session.id);
+            foj
+        })
+        .map_err(|e| match e {
+            SimpleError::Unavailable => e,
+            _ => "Authentication Failed!".into(),
+        })
+        .and_then(move |foj| {
+            fetch_problems(board_prob, foj.clone(), gids_prob, columns_prob).map(move |_| foj)
+        })
+        .and_then(move |foj| {
+            let foj_arc = foj.clone();
+            let start = Instant::now();
+            fetch_group(board.clone(), foj_arc.clone(), config).map(move |_| {
+                debug!("Fetched submissions in {:?}", start.elapsed());
+                board.log_problem_mismatch();
+                foj
+            })
+        })
+        .and_then(move |foj| {
+            let start = Instant::now();
+            update_name(board_arc, foj)
+                .map(move |_| debug!("Resolved user names in {:?}", start.elapsed()))
+        })
+        .map_err(move |e: SimpleError| {
+            // The token may have expired since the session was cached
+            if e.is_unauthorized() {
+                foj_err.invalidate_session();
+            }
+            e
+        })
+}
+
+fn fetch_problems(
+    board: Arc<Scoreboard>,
+    foj: Arc<FojApi>,
+    gids: Vec<u32>,
+    columns: Option<ProblemColumns>,
+) -> impl Future<Item = (), Error = SimpleError> {
+    let futures_iter = gids
+        .clone()
+        .into_iter()
+        .map(move |gid| foj.get_problem_list(gid));
+    futures::future::join_all(futures_iter).map(move |lists| {
+        *lock(&board.problem_info) = gids
+            .into_iter()
+            .zip(lists)
+            .flat_map(|(gid, list)| list.into_iter().map(move |p| (gid, p)))
+            .filter_map(|(gid, p)| match &columns {
+                Some(columns) => Some((columns.column(gid, p.id)?, p)),
+                None => Some((p.id, p)),
+            })
+            .collect();
+    })
+}
+
+/// Fetch every group concurrently, then merge them in one pass so that
+/// the cache time watermark stays consistent across groups
+///
+/// Those outside of `window` are dropped before being counted.
+fn fetch_group(
+    board: Arc<Scoreboard>,
+    foj: Arc<FojApi>,
+    config: SyncConfig,
+) -> impl Future<Item = (), Error = SimpleError> {
+    let problem = config.problem;
+    let mut keys: Vec<String> = config
+        .gids
+        .iter()
+        .map(|&gid| list_key("group", gid, problem))
+        .collect();
+    keys.extend(config.contest.map(|cid| list_key("contest", cid, None)));
+    let checkpoints: Vec<Checkpoint> = keys.iter().map(|key| board.checkpoint(key)).collect();
+    let lists = fetch_lists(&foj, &config, checkpoints);
+    let (overlap, window) = (config.overlap, config.window);
+    let (accepted, rejected) = (config.accepted, config.rejected);
+    lists.and_then(move |lists| {
+        let (count, submissions) = merge_lists(lists, problem, window);
+        *write(&board.fetch_count) = Some(count);
+        save_submissions(board.clone(), submissions, overlap, &accepted, &rejected)?;
+        // Everything received has been counted now
+        let mut checkpoints = lock(&board.checkpoints);
+        for key in &keys {
+            checkpoints.remove(key);
+        }
+        Ok(())
+    })
+}
+
+/// Every submission a sync with `config` counts, merged the same way,
+/// without touching a board
+pub fn fetch_submissions(
+    foj: &FojApi,
+    config: &SyncConfig,
+) -> impl Future<Item = Vec<Submission>, Error = SimpleError> {
+    let lists = config.gids.len() + config.contest.iter().count();
+    let checkpoints = (0..lists).map(|_| Checkpoint::default()).collect();
+    let (problem, window) = (config.problem, config.window);
+    fetch_lists(foj, config, checkpoints).map(move |lists| merge_lists(lists, problem, window).1)
+}
+
+/// The submission list of each group, then the contest's, going on from
+/// `checkpoints` in the same order
+///
+/// With `problem` set only that problem's submissions are fetched.
+/// A contest's submissions are merged in as well when configured.
+/// With `columns` set, each group's submissions go to that group's columns.
+fn fetch_lists(
+    foj: &FojApi,
+    config: &SyncConfig,
+    mut checkpoints: Vec<Checkpoint>,
+) -> impl Future<Item = Vec<(usize, Vec<Submission>)>, Error = SimpleError> {
+    let contest_future = match config.contest {
+        Some(cid) => {
+            let checkpoint = checkpoints.pop().unwrap_or_default();
+            Either::A(
+                foj.get_submission_contest_counted(cid, checkpoint)
+                    .map(Some),
+            )
+        }
+        None => Either::B(futures::future::ok(None)),
+    };
+    let problem = config.problem;
+    let group_futures: Vec<_> = config
+        .gids
+        .iter()
+        .copied()
+        .zip(checkpoints)
+        .map(|(gid, checkpoint)| {
+            let list = match problem {
+                Some(pid) => Either::A(foj.get_submission_prob_counted(gid, pid, checkpoint)),
+                None => Either::B(foj.get_submission_group_counted(gid, checkpoint)),
+            };
+            let columns = config.columns.clone();
+            list.map(move |(count, subs)| match columns {
+                Some(columns) => (count, to_columns(&columns, gid, subs)),
+                None => (count, subs),
+            })
+        })
+        .collect();
+    futures::future::join_all(group_futures)
+        .join(contest_future)
+        .map(|(mut lists, contest)| {
+            // A contest's submissions may be cut short by paging just the same
+            lists.extend(contest);
+            lists
+        })
+}
 
-        table
+/// Join the lists of every group and the contest into one list of
+/// submissions, each counted once
+fn merge_lists(
+    lists: Vec<(usize, Vec<Submission>)>,
+    problem: Option<u32>,
+    window: TimeWindow,
+) -> (FetchCount, Vec<Submission>) {
+    let count = FetchCount {
+        reported: lists.iter().map(|(count, _)| count).sum(),
+        received: lists.iter().map(|(_, subs)| subs.len()).sum(),
+    };
+    if count.reported != count.received {
+        warn!(
+            "The judge reported {} submissions, {} were received",
+            count.reported, count.received
+        );
+    }
+    let mut submissions: Vec<Submission> = lists.into_iter().flat_map(|(_, subs)| subs).collect();
+    if let Some(pid) = problem {
+        submissions.retain(|sub| sub.problem_id == pid);
+    }
+    // A submission may belong to both a group and the contest. Kept apart
+    // per group, it is counted once in each group's column.
+    submissions.sort_by_key(|sub| (sub.id, sub.problem_id));
+    submissions.dedup_by_key(|sub| (sub.id, sub.problem_id));
+    if !window.is_unbounded() {
+        let total = submissions.len();
+        submissions.retain(|sub| window.contains(sub.created_at));
+        debug!(
+            "{} of {} submissions fall inside the window",
+            submissions.len(),
+            total
+        );
     }
+    (count, submissions)
 }
 
-pub fn sync(
-    board: Arc<Scoreboard>,
-    gid: u32,
-    token: String,
-) -> impl Future<Item = (), Error = SimpleError> + 'static {
-    let board_arc = board.clone();
-    futures::future::result(FojApi::new(token))
-        .and_then(|foj| {
-            foj.session()
-                .map(|session| {
-                    info!("Authentication Succuss!");
-                    trace!("{:?}", session);
-                    Arc::new(foj)
-                })
-                .map_err(|_| "Authentication Failed!".into())
-        })
-        .and_then(move |foj| {
-            let foj_arc = foj.clone();
-            fetch_group(board.clone(), foj_arc.clone(), gid).map(move |_| foj)
+/// Move the submissions listed by group `gid` to its problem columns
+fn to_columns(columns: &ProblemColumns, gid: u32, subs: Vec<Submission>) -> Vec<Submission> {
+    subs.into_iter()
+        .filter_map(|mut sub| match columns.column(gid, sub.problem_id) {
+            Some(column) => {
+                sub.problem_id = column;
+                Some(sub)
+            }
+            None => {
+                warn!(
+                    "Problem {} of group {} has too large an id for its own column",
+                    sub.problem_id, gid
+                );
+                None
+            }
         })
-        .and_then(move |foj| update_name(board_arc, foj))
+        .collect()
 }
 
-fn fetch_group(
-    board: Arc<Scoreboard>,
-    foj: Arc<FojApi>,
-    gid: u32,
-) -> impl Future<Item = (), Error = SimpleError> {
-    foj.get_submission_group(gid)
-        .map(move |mut submissions| {
-            submissions.sort_by(|a, b| a.created_at.cmp(&b.created_at));
-            submissions
-        })
-        .and_then(move |submissions| save_submissions(board, submissions))
+/// Names the submission list a checkpoint belongs to, like `group 3`
+fn list_key(scope: &str, id: u32, problem: Option<u32>) -> String {
+    match problem {
+        Some(pid) => format!("{} {} problem {}", scope, id, pid),
+        None => format!("{} {}", scope, id),
+    }
 }
 
-fn save_submissions(board: Arc<Scoreboard>, submissions: Vec<Submission>) -> SimpleResult<()> {
-    let time_lock = board.cache_time.read().unwrap();
-    let mut new_time = *time_lock;
+/// Merge the submissions not counted yet, counting the verdict ids in
+/// `accepted` as solves and those in `rejected` as wrong tries
+///
+/// Ids grow with each submission, so the board resumes after the highest id
+/// it has counted. Several submissions often share a second, which made a
+/// time watermark skip or count some twice. One still being judged holds the
+/// watermark back, and those counted after it are told apart by id.
+///
+/// Boards from before the id watermark resume once from the cache time,
+/// minus `overlap` to cover clock differences with the judge. With no ids to
+/// go by, a submission in the look-back is only counted on a cell the board
+/// doesn't have yet, as one it already has may hold it.
+fn save_submissions(
+    board: Arc<Scoreboard>,
+    mut submissions: Vec<Submission>,
+    overlap: Duration,
+    accepted: &[u32],
+    rejected: &[u32],
+) -> SimpleResult<()> {
+    // Counted in the order they were made, which the tries before an AC and
+    // the id watermark below rely on. Sorted here rather than by the caller,
+    // as the judge lists them newest first and merged lists come in any order.
+    submissions.sort_by_key(|sub| sub.id);
 
-    let start_from = match submissions.binary_search_by(|sub| sub.created_at.cmp(&time_lock)) {
-        Ok(p) => p + 1,
-        Err(p) => p,
+    let _sync = lock(&board.sync_lock);
+    let time_lock = read(&board.cache_time);
+    let mut new_time = *time_lock;
+    let since = *time_lock - overlap;
+    let mut seen_lock = write(&board.seen_through);
+    let mut boundary_lock = lock(&board.boundary_ids);
+    let mut user_lock = lock(&board.user_map);
+    let counted_cell = |sub: &Submission| {
+        user_lock
+            .get(&sub.user_id)
+            .is_some_and(|user| user.problems.contains_key(&sub.problem_id))
     };
+    let fresh: Vec<&Submission> = submissions
+        .iter()
+        .filter(|sub| !boundary_lock.contains(&sub.id))
+        .filter(|sub| match *seen_lock {
+            Some(id) => sub.id > id,
+            None if sub.created_at <= *time_lock => sub.created_at > since && !counted_cell(sub),
+            None => true,
+        })
+        .collect();
 
-    let mut user_lock = board.user_map.lock().unwrap();
-    let mut problems_lock = board.problem_set.lock().unwrap();
+    let mut problems_lock = lock(&board.problem_set);
+    let mut feed_lock = lock(&board.feed);
+    let mut first_lock = lock(&board.first_solver);
 
-    for sub in &submissions[start_from..] {
+    for sub in fresh.iter().copied() {
         let user_record: &mut UserRecord = user_lock.entry(sub.user_id).or_default();
         let pid = sub.problem_id;
+        if user_record.last_submit < Some(sub.created_at) {
+            user_record.last_submit = Some(sub.created_at);
+        }
+        let cell = user_record.problem(pid);
+        if cell.last_try_at < Some(sub.created_at) {
+            cell.last_try_at = Some(sub.created_at);
+        }
 
         if !problems_lock.contains(&pid) {
             problems_lock.insert(pid);
         }
 
-        match sub.verdict_id as u32 {
-            4..=9 => {
-                if user_record.problem(pid).status != SolveStatus::Accepted {
-                    user_record.problem(pid).status = SolveStatus::WrongAnswer;
-                    user_record.problem(pid).wa_count += 1;
+        let event = |status| FeedEvent {
+            time: sub.created_at,
+            user_id: sub.user_id,
+            problem_id: pid,
+            status,
+        };
+        match sub.verdict_id.id() {
+            v if accepted.contains(&v) => {
+                let cell = user_record.problem(pid);
+                cell.attempts += 1;
+                cell.best_score = cell.best_score.max(sub.score);
+                if cell.status != SolveStatus::Accepted {
+                    cell.solved_at = Some(sub.created_at);
+                    feed_lock.push_back(event(SolveStatus::Accepted));
+                }
+                cell.status = SolveStatus::Accepted;
+                // One held back while judging may still beat the known one
+                let solve = FirstSolve {
+                    user_id: sub.user_id,
+                    time: sub.created_at,
+                    submission_id: sub.id,
+                };
+                match first_lock.get(&pid) {
+                    Some(known) if !solve.is_before(known) => {}
+                    _ => {
+                        first_lock.insert(pid, solve);
+                    }
                 }
                 if sub.created_at > new_time {
                     new_time = sub.created_at;
                 }
             }
-            10 => {
-                user_record.problem(pid).status = SolveStatus::Accepted;
+            v if rejected.contains(&v) => {
+                let cell = user_record.problem(pid);
+                cell.attempts += 1;
+                // Subtasks may pass even though the verdict is a failing one
+                cell.best_score = cell.best_score.max(sub.score);
+                if cell.status != SolveStatus::Accepted {
+                    cell.status = SolveStatus::WrongAnswer;
+                    cell.wa_count += 1;
+                    cell.last_verdict = Some(sub.verdict_id);
+                    feed_lock.push_back(event(SolveStatus::WrongAnswer));
+                }
                 if sub.created_at > new_time {
                     new_time = sub.created_at;
                 }
             }
-            _ => {}
+            _ => {
+                // Remember the attempt even though it doesn't count
+                if user_record.problem(pid).status == SolveStatus::None {
+                    user_record.problem(pid).status = SolveStatus::Attempted;
+                }
+            }
+        }
+    }
+
+    // Every sync sees the whole list, so a judged submission drops its mark
+    let unjudged: BTreeSet<(u32, u32)> = submissions
+        .iter()
+        .filter(|sub| matches!(sub.verdict_id, Verdict::Pending | Verdict::Judging))
+        .map(|sub| (sub.user_id, sub.problem_id))
+        .collect();
+    // Those still unjudged are seen again each sync, so only their mark counts
+    let mut changed = fresh
+        .iter()
+        .any(|sub| !matches!(sub.verdict_id, Verdict::Pending | Verdict::Judging));
+    for (&uid, user) in user_lock.iter_mut() {
+        for (&pid, cell) in user.problems.iter_mut() {
+            let pending = unjudged.contains(&(uid, pid)) && cell.status != SolveStatus::Accepted;
+            changed |= cell.pending != pending;
+            cell.pending = pending;
         }
     }
+    if changed {
+        board.revision.fetch_add(1, AtomicOrdering::SeqCst);
+    }
+
+    while feed_lock.len() > FEED_CAPACITY {
+        feed_lock.pop_front();
+    }
+    // Unjudged ones have to be seen again once judged
+    let judged = |sub: &Submission| !matches!(sub.verdict_id, Verdict::Pending | Verdict::Judging);
+    let watermark = match fresh.iter().find(|sub| !judged(sub)) {
+        Some(sub) => sub.id - 1,
+        None => submissions
+            .last()
+            .map(|sub| sub.id)
+            .max(*seen_lock)
+            .unwrap_or(0),
+    };
+    *boundary_lock = submissions
+        .iter()
+        .filter(|sub| sub.id > watermark && judged(sub))
+        .map(|sub| sub.id)
+        .collect();
+    *seen_lock = Some(watermark);
 
     drop(time_lock);
-    let mut time_entry = board.cache_time.write().unwrap();
+    let mut time_entry = write(&board.cache_time);
     if new_time > *time_entry {
         *time_entry = new_time;
     }
@@ -202,35 +2621,42 @@ fn update_name(
     board: Arc<Scoreboard>,
     foj: Arc<FojApi>,
 ) -> impl Future<Item = (), Error = SimpleError> {
-    let name_update_list: Vec<u32> = board
-        .user_map
-        .lock()
-        .unwrap()
+    let name_update_list = names_to_update(&board);
+    foj.get_user_names(&name_update_list)
+        .map(move |names| save_names(&board, &name_update_list, names))
+}
+
+/// The users without a name which have not come back empty before
+fn names_to_update(board: &Scoreboard) -> Vec<u32> {
+    lock(&board.user_map)
         .iter()
         .filter_map(|(&uid, user)| {
-            if user.name.is_empty() {
+            if user.name.is_empty() && !user.name_missing {
                 Some(uid)
             } else {
                 None
             }
         })
-        .collect();
-    let futures_iter = name_update_list.into_iter().map(move |uid| {
-        let board = board.clone();
-        foj.get_user_name(uid)
-            .map(move |name| (uid, name))
-            .map(move |(uid, name)| {
-                board
-                    .user_map
-                    .lock()
-                    .unwrap()
-                    .entry(uid)
-                    .and_modify(|user| {
-                        user.name = name;
-                    });
-            })
-    });
-    futures::future::join_all(futures_iter).map(|_| ())
+        .collect()
+}
+
+/// Store the names looked up for `asked`, an empty or missing one keeps
+/// whatever name the user had and marks it as not worth asking again
+fn save_names(board: &Scoreboard, asked: &[u32], mut names: BTreeMap<u32, String>) {
+    let mut user_lock = lock(&board.user_map);
+    for uid in asked {
+        let user = match user_lock.get_mut(uid) {
+            Some(user) => user,
+            None => continue,
+        };
+        match names.remove(uid).filter(|name| !name.trim().is_empty()) {
+            Some(name) => user.name = name,
+            None => {
+                debug!("No name for user {}, showing the id", uid);
+                user.name_missing = true;
+            }
+        }
+    }
 }
 
 impl Default for Scoreboard {
@@ -239,64 +2665,1123 @@ impl Default for Scoreboard {
     }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 struct UserRecord {
     id: u32,
     name: String,
+    /// The judge had no name for them, so it isn't asked again
+    name_missing: bool,
     problems: BTreeMap<u32, ProblemCell>,
+    last_submit: Option<DateTime<Local>>,
+    /// Free text from the coach, kept across syncs
+    notes: Option<String>,
 }
 
 impl UserRecord {
-    fn ac_count(&self, prob_set: &BTreeSet<u32>) -> usize {
-        let mut count = 0;
-        for prob in prob_set {
-            if let Some(cell) = self.problems.get(prob) {
-                if cell.status == SolveStatus::Accepted {
-                    count += 1;
+    fn ac_count(&self, prob_list: &[u32]) -> usize {
+        self.solved_among(prob_list.iter())
+    }
+
+    fn solved_among<'a, I: Iterator<Item = &'a u32>>(&self, problems: I) -> usize {
+        problems
+            .filter_map(|prob| self.problems.get(prob))
+            .filter(|cell| cell.status == SolveStatus::Accepted)
+            .count()
+    }
+
+    /// The time this user reached their current AC count
+    fn last_solve_time(&self, prob_list: &[u32]) -> Option<DateTime<Local>> {
+        prob_list
+            .iter()
+            .filter_map(|prob| self.problems.get(prob))
+            .filter(|cell| cell.status == SolveStatus::Accepted)
+            .filter_map(|cell| cell.solved_at)
+            .max()
+    }
+
+    /// The best scores on `prob_list` added up as `Metadata::scaled_score`
+    /// says, problems without one count as 0
+    fn score(&self, prob_list: &[u32], meta: &Metadata) -> i64 {
+        prob_list
+            .iter()
+            .filter_map(|&prob| Some((prob, self.problems.get(&prob)?.best_score?)))
+            .map(|(prob, score)| meta.scaled_score(prob, score))
+            .sum()
+    }
+
+    /// Minutes from `contest_start` to each AC in `prob_list`, plus
+    /// `PENALTY_MINUTES` per wrong try before it
+    ///
+    /// Wrong tries on unsolved problems count only with `PenaltyPolicy::AllTries`,
+    /// never those of a `Frozen` cell as they may come after the freeze.
+    fn penalty(
+        &self,
+        prob_list: &[u32],
+        contest_start: DateTime<Local>,
+        rounding: MinuteRounding,
+        policy: PenaltyPolicy,
+    ) -> i64 {
+        prob_list
+            .iter()
+            .filter_map(|prob| self.problems.get(prob))
+            .filter(|cell| cell.status != SolveStatus::Frozen)
+            .map(|cell| {
+                let tries = PENALTY_MINUTES * cell.wa_count as i64;
+                match cell.solved_at {
+                    Some(at) if cell.status == SolveStatus::Accepted => {
+                        rounding.minutes(at - contest_start) + tries
+                    }
+                    _ if policy == PenaltyPolicy::AllTries => tries,
+                    _ => 0,
                 }
-            }
-        }
-        count
+            })
+            .sum()
     }
 
     fn problem(&mut self, prob_id: u32) -> &mut ProblemCell {
         self.problems.entry(prob_id).or_default()
     }
+
+    /// See `ProblemCell::frozen_at`
+    fn frozen_at(&self, freeze: DateTime<Local>) -> Self {
+        let problems = self
+            .problems
+            .iter()
+            .map(|(&pid, cell)| (pid, cell.frozen_at(freeze, self.last_submit)))
+            .collect();
+        UserRecord {
+            problems,
+            ..self.clone()
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 struct ProblemCell {
     wa_count: usize,
     status: SolveStatus,
+    solved_at: Option<DateTime<Local>>,
+    /// Every judged submission, including repeated ACs
+    attempts: usize,
+    /// The verdict of the latest failing submission
+    last_verdict: Option<Verdict>,
+    /// A submission is still pending or judging, and there is no AC yet
+    pending: bool,
+    /// The best score among the judged submissions, when the judge gives one
+    best_score: Option<i32>,
+    /// When the latest submission was made, judged or not
+    last_try_at: Option<DateTime<Local>>,
+}
+
+impl ProblemCell {
+    /// The status to print, `WA` unless `show_verdicts` asks for the detail
+    fn status_text(&self, show_verdicts: bool) -> &'static str {
+        match (self.status, self.last_verdict) {
+            (SolveStatus::WrongAnswer, Some(v)) if show_verdicts => v.abbr(),
+            (status, _) => status.abbr(),
+        }
+    }
+
+    /// The cell as the board shows it while frozen at `freeze`
+    ///
+    /// An AC from before then stays. Tried since, it turns `Frozen` with its
+    /// verdict, solve time and score hidden, and every try still counted.
+    /// Cells from older caches fall back to the user's `last_submit`.
+    fn frozen_at(&self, freeze: DateTime<Local>, last_submit: Option<DateTime<Local>>) -> Self {
+        let solved_before = matches!(self.solved_at, Some(at) if at < freeze)
+            && self.status == SolveStatus::Accepted;
+        let tried_since = matches!(self.last_try_at.or(last_submit), Some(at) if at >= freeze);
+        if solved_before || !tried_since {
+            return *self;
+        }
+        ProblemCell {
+            status: SolveStatus::Frozen,
+            solved_at: None,
+            last_verdict: None,
+            best_score: None,
+            ..*self
+        }
+    }
+
+    /// The number printed after the status, WA, WA, AC shows `AC / 3` as tries
+    fn tries(&self, count: TryCount) -> usize {
+        let tries = match self.status {
+            SolveStatus::Accepted => self.wa_count + 1,
+            _ => self.wa_count,
+        };
+        match count {
+            TryCount::Tries => tries,
+            TryCount::Before => tries.saturating_sub(1),
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum SolveStatus {
+    #[default]
     None = 0,
     Accepted,
     WrongAnswer,
+    Attempted,
+    /// Tried since `freeze_time`, only ever shown, see `ProblemCell::frozen_at`
+    Frozen,
+}
+
+impl SolveStatus {
+    fn abbr(self) -> &'static str {
+        match self {
+            SolveStatus::Accepted => "AC",
+            SolveStatus::WrongAnswer => "WA",
+            SolveStatus::Attempted => "AT",
+            SolveStatus::Frozen => "?",
+            SolveStatus::None => "NS",
+        }
+    }
+
+    /// Like `abbr`, the exporters only tell AC, WA and frozen cells from the rest
+    fn export_abbr(self) -> &'static str {
+        match self {
+            SolveStatus::Accepted => "AC",
+            SolveStatus::WrongAnswer => "WA",
+            SolveStatus::Frozen => "?",
+            _ => "NS",
+        }
+    }
 }
 
 impl fmt::Display for SolveStatus {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if !f.alternate() {
-            match self {
-                SolveStatus::Accepted => write!(f, "AC"),
-                SolveStatus::WrongAnswer => write!(f, "WA"),
-                SolveStatus::None => write!(f, "NS"),
-            }
+            f.write_str(self.abbr())
         } else {
             match self {
                 SolveStatus::Accepted => write!(f, "Accepted"),
                 SolveStatus::WrongAnswer => write!(f, "Wrong Answer"),
+                SolveStatus::Attempted => write!(f, "Attempted"),
+                SolveStatus::Frozen => write!(f, "Frozen"),
                 SolveStatus::None => write!(f, "None"),
             }
         }
     }
 }
 
-impl Default for SolveStatus {
-    fn default() -> Self {
-        SolveStatus::None
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCEPTED: &[u32] = &[Verdict::AC.id()];
+    const REJECTED: &[u32] = &[5, 6, 7, 8, 9];
+
+    fn meta(extra: &str) -> Metadata {
+        toml::from_str(&format!("group_id = 1\nuser_token = \"\"\n{}", extra)).unwrap()
+    }
+
+    fn at(sec: i64) -> DateTime<Local> {
+        Local.timestamp(1_600_000_000 + sec, 0)
+    }
+
+    fn sub(id: u64, user_id: u32, problem_id: u32, verdict_id: Verdict, sec: i64) -> Submission {
+        Submission {
+            memory_usage: None,
+            time_usage: None,
+            length: 0,
+            verdict_id,
+            execute_id: 0,
+            user_id,
+            problem_id,
+            created_at: at(sec),
+            updated_at: at(sec),
+            id,
+            score: None,
+        }
+    }
+
+    fn board_of(subs: Vec<Submission>) -> Arc<Scoreboard> {
+        let board = Arc::new(Scoreboard::new());
+        *write(&board.cache_time) = at(-60);
+        save(&board, subs);
+        board
+    }
+
+    fn save(board: &Arc<Scoreboard>, subs: Vec<Submission>) {
+        save_submissions(
+            board.clone(),
+            subs,
+            Duration::seconds(5),
+            ACCEPTED,
+            REJECTED,
+        )
+        .unwrap();
+    }
+
+    /// The text of each user row, keyed by the name in its first cell
+    fn rows(table: &Table) -> BTreeMap<String, Vec<String>> {
+        table
+            .row_iter()
+            .map(|row| row.iter().map(|c| c.get_content()).collect::<Vec<_>>())
+            .filter(|cells| cells.len() > 1 && !cells[0].is_empty() && cells[0] != "Updated At")
+            .map(|cells| (cells[0].clone(), cells[1..].to_vec()))
+            .collect()
+    }
+
+    /// The names in the first cell of the user rows, top to bottom
+    fn row_names(table: &Table) -> Vec<String> {
+        table
+            .row_iter()
+            .map(|row| row.get_cell(0).unwrap().get_content())
+            .filter(|name| !name.is_empty() && name != "Updated At")
+            .collect()
+    }
+
+    /// Every cell of the board by user and problem
+    fn cells(board: &Scoreboard) -> BTreeMap<(u32, u32), ProblemCell> {
+        lock(&board.user_map)
+            .iter()
+            .flat_map(|(&uid, user)| user.problems.iter().map(move |(&pid, &c)| ((uid, pid), c)))
+            .collect()
+    }
+
+    fn name(board: &Scoreboard, uid: u32, name: &str) {
+        lock(&board.user_map).get_mut(&uid).unwrap().name = name.to_owned();
+    }
+
+    #[test]
+    fn star_goes_to_the_earlier_of_two_close_solves() {
+        let board = board_of(vec![
+            sub(1, 2, 101, Verdict::AC, 3),
+            sub(2, 1, 101, Verdict::AC, 1),
+        ]);
+        name(&board, 1, "Alice");
+        name(&board, 2, "Bob");
+        let rows = rows(&board.gen_table(&meta(""), &TableOptions::default()));
+        assert_eq!(rows["Alice"][0], "AC / 1 ★");
+        assert_eq!(rows["Bob"][0], "AC / 1");
+    }
+
+    #[test]
+    fn star_skips_excluded_users() {
+        let board = board_of(vec![
+            sub(1, 9, 101, Verdict::AC, 0),
+            sub(2, 1, 101, Verdict::AC, 2),
+        ]);
+        name(&board, 9, "Instructor");
+        name(&board, 1, "Alice");
+        let rows = rows(&board.gen_table(&meta("exclude_users = [9]"), &TableOptions::default()));
+        assert!(!rows.contains_key("Instructor"));
+        assert_eq!(rows["Alice"][0], "AC / 1 ★");
+    }
+
+    #[test]
+    fn export_rows_follow_the_board() {
+        let board = board_of(vec![
+            sub(1, 9, 101, Verdict::AC, 0),
+            sub(2, 1, 101, Verdict::WA, 1),
+            sub(3, 2, 101, Verdict::AC, 2),
+            sub(4, 1, 102, Verdict::CE, 3),
+            sub(5, 3, 103, Verdict::AC, 4),
+        ]);
+        let meta = meta("exclude_users = [9]\nexclude_problems = [103]\nname_display = \"id\"");
+        let csv = board.export_csv(&meta, &TableOptions::default());
+        assert_eq!(
+            csv,
+            "rank,user_id,name,101,101_wa,102,102_wa,solved,penalty\n\
+             1,2,User #2,AC,0,NS,0,1,\n\
+             2,1,User #1,WA,1,NS,0,0,\n"
+        );
+    }
+
+    #[test]
+    fn export_ranks_match_the_board_order() {
+        let board = board_of(vec![
+            sub(1, 1, 101, Verdict::WA, 0),
+            sub(2, 1, 101, Verdict::AC, 60),
+            sub(3, 2, 101, Verdict::AC, 120),
+            sub(4, 3, 101, Verdict::AC, 180),
+            sub(5, 3, 102, Verdict::AC, 240),
+        ]);
+        for (uid, n) in [(1, "Alice"), (2, "Bob"), (3, "Carol")] {
+            name(&board, uid, n);
+        }
+        let meta = meta("sort_mode = \"penalty\"\ncontest_start = \"2020-09-13T12:00:00Z\"");
+        let filtered = TableOptions {
+            filter: Some((102, StatusFilter::Unsolved)),
+            ..TableOptions::default()
+        };
+        for opts in [TableOptions::default(), filtered] {
+            let shown = row_names(&board.gen_table(&meta, &opts));
+            let (_, rows) = board.export_rows(&meta, &opts);
+            let exported: Vec<String> = rows.iter().map(|row| row.name.clone()).collect();
+            assert_eq!(exported, shown);
+        }
+
+        let (_, rows) = board.export_rows(&meta, &filtered);
+        let ranks: Vec<(Option<usize>, Option<&ExportPenalty>)> = rows
+            .iter()
+            .map(|row| (row.rank, row.penalty.as_ref()))
+            .collect();
+        // Bob solved later, but Alice has a wrong try, Carol is filtered out
+        assert_eq!(
+            ranks,
+            [
+                (Some(2), Some(&ExportPenalty::Minutes(28))),
+                (Some(3), Some(&ExportPenalty::Minutes(47)))
+            ]
+        );
+    }
+
+    #[test]
+    fn penalty_policy_decides_on_unsolved_tries() {
+        let board = board_of(vec![
+            sub(1, 1, 101, Verdict::WA, 0),
+            sub(2, 1, 101, Verdict::AC, 60),
+            sub(3, 1, 102, Verdict::WA, 120),
+            sub(4, 1, 102, Verdict::WA, 180),
+        ]);
+        let user = lock(&board.user_map)[&1].clone();
+        let start = Local.timestamp(1_600_000_000 - 60, 0);
+        let problems = [101, 102];
+        let penalty = |policy| user.penalty(&problems, start, MinuteRounding::Floor, policy);
+        // Solved 2 minutes in after one wrong try
+        assert_eq!(penalty(PenaltyPolicy::SolvedOnly), 2 + 20);
+        assert_eq!(penalty(PenaltyPolicy::AllTries), 2 + 20 + 2 * 20);
+    }
+
+    #[test]
+    fn penalty_shows_as_hours_and_minutes() {
+        assert_eq!(PenaltyFormat::HoursMinutes.show(743), "12:23");
+        assert_eq!(PenaltyFormat::HoursMinutes.show(5), "0:05");
+        assert_eq!(PenaltyFormat::HoursMinutes.show(0), "0:00");
+        assert_eq!(PenaltyFormat::HoursMinutes.show(-7), "0:00");
+        assert_eq!(PenaltyFormat::Minutes.show(743), "743");
+
+        let board = board_of(vec![sub(1, 1, 101, Verdict::AC, 0)]);
+        let meta = meta(
+            "contest_start = \"2020-09-13T10:00:00Z\"\nshow_penalty = true\n\
+             penalty_format = \"hours_minutes\"",
+        );
+        let csv = board.export_csv(&meta, &TableOptions::default());
+        assert!(csv.ends_with(",1,2:26\n"), "{}", csv);
+        let json = board.export_json(&meta, &TableOptions::default()).unwrap();
+        assert!(json.contains("\"penalty\": \"2:26\""), "{}", json);
+        let rows = rows(&board.gen_table(&meta, &TableOptions::default()));
+        assert_eq!(rows["User #1"].last().unwrap(), "2:26");
+    }
+
+    #[test]
+    fn frozen_tries_stay_out_of_the_penalty() {
+        let board = board_of(vec![
+            sub(1, 1, 101, Verdict::WA, 100),
+            sub(2, 1, 101, Verdict::WA, 300),
+            sub(3, 1, 102, Verdict::WA, 150),
+        ]);
+        // 12:30:00 is 200 seconds in, after the first try on each problem
+        let user = lock(&board.user_map)[&1].frozen_at(at(200));
+        assert_eq!(user.problems[&101].status, SolveStatus::Frozen);
+        let penalty = user.penalty(
+            &[101, 102],
+            at(0),
+            MinuteRounding::Floor,
+            PenaltyPolicy::AllTries,
+        );
+        assert_eq!(penalty, PENALTY_MINUTES);
+
+        let meta = meta(
+            "freeze_time = \"2020-09-13T12:30:00Z\"\n\
+             contest_start = \"2020-09-13T12:26:40Z\"\n\
+             penalty_policy = \"all_tries\"",
+        );
+        let csv = board.export_csv(&meta, &TableOptions::default());
+        assert!(
+            csv.ends_with(&format!(",0,{}\n", PENALTY_MINUTES)),
+            "{}",
+            csv
+        );
+    }
+
+    #[test]
+    fn frozen_results_stay_out_of_exports() {
+        let board = board_of(vec![
+            sub(1, 1, 101, Verdict::AC, 0),
+            sub(2, 2, 101, Verdict::WA, 100),
+            sub(3, 2, 101, Verdict::AC, 400),
+        ]);
+        // Both sent before the freeze, only the late AC comes after it
+        let meta = meta("freeze_time = \"2020-09-13T12:30:00Z\"");
+        let csv = board.export_csv(&meta, &TableOptions::default());
+        assert_eq!(
+            csv,
+            "rank,user_id,name,101,101_wa,solved,penalty\n\
+             1,1,User #1,AC,0,1,\n\
+             2,2,User #2,?,0,0,\n"
+        );
+        let json = board.export_json(&meta, &TableOptions::default()).unwrap();
+        assert_eq!(json.matches("\"AC\"").count(), 1, "{}", json);
+        let report = board
+            .export_user_report(2, ReportFormat::Csv, &meta)
+            .unwrap();
+        assert!(report.contains(",Frozen,2,0,\n"), "{}", report);
+        let summary = board.coverage_summary(&meta);
+        assert!(summary.solved_by_all.is_empty());
+    }
+
+    #[test]
+    fn freeze_starts_at_its_exact_second() {
+        // 12:30:00 is 200 seconds in
+        let board = board_of(vec![
+            sub(1, 1, 101, Verdict::AC, 199),
+            sub(2, 2, 101, Verdict::AC, 200),
+            sub(3, 3, 101, Verdict::AC, 201),
+        ]);
+        let meta = meta("freeze_time = \"2020-09-13T12:30:00Z\"");
+        let csv = board.export_csv(&meta, &TableOptions::default());
+        let status = |uid: u32| {
+            let line = csv
+                .lines()
+                .find(|line| line.split(',').nth(1) == Some(&uid.to_string()))
+                .unwrap();
+            line.split(',').nth(3).unwrap().to_owned()
+        };
+        assert_eq!(status(1), "AC");
+        assert_eq!(status(2), "?");
+        assert_eq!(status(3), "?");
+
+        let shown = |uid: u32| lock(&board.user_map)[&uid].frozen_at(at(200)).problems[&101];
+        assert_eq!(shown(1).status, SolveStatus::Accepted);
+        assert_eq!(shown(2).status, SolveStatus::Frozen);
+        assert_eq!(shown(3).status, SolveStatus::Frozen);
+        // Kept by the cache for when the board unfreezes
+        assert_eq!(cells(&board)[&(2, 101)].status, SolveStatus::Accepted);
+    }
+
+    #[test]
+    fn patch_brings_the_base_up_to_date() {
+        let base = board_of(vec![
+            sub(1, 1, 101, Verdict::WA, 0),
+            sub(2, 2, 101, Verdict::AC, 10),
+        ]);
+        let copy = |board: &Scoreboard| -> Scoreboard {
+            bincode::deserialize(&bincode::serialize(board).unwrap()).unwrap()
+        };
+        let board = Arc::new(copy(&base));
+        save(
+            &board,
+            vec![
+                sub(3, 1, 101, Verdict::AC, 20),
+                sub(4, 3, 102, Verdict::WA, 30),
+            ],
+        );
+        name(&board, 2, "Bob");
+        let checkpoint = r#"{"reported":5000,"rows":1000,"submissions":[]}"#;
+        lock(&board.checkpoints).insert("g1".to_owned(), serde_json::from_str(checkpoint).unwrap());
+
+        let mut patched = copy(&base);
+        patched
+            .apply_patch(&board.diff_patch(&base).unwrap())
+            .unwrap();
+        assert_eq!(
+            bincode::serialize(&patched).unwrap(),
+            bincode::serialize(&*board).unwrap()
+        );
+        assert!(!lock(&patched.checkpoints)["g1"].is_empty());
+    }
+
+    /// Load `old` written as a cache, the way an older build laid it out
+    fn load_legacy<T: Serialize>(old: &T, file: &str) -> Scoreboard {
+        let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), file));
+        fs::write(&path, bincode::serialize(old).unwrap()).unwrap();
+        let board = Scoreboard::load_cache(&path);
+        let _ = fs::remove_file(&path);
+        board.unwrap()
+    }
+
+    #[test]
+    fn loads_the_first_cache_layout() {
+        // Users of id, name and cells of wrong tries and status
+        let cells: BTreeMap<u32, (usize, SolveStatus)> = vec![
+            (101, (2, SolveStatus::Accepted)),
+            (102, (1, SolveStatus::WrongAnswer)),
+        ]
+        .into_iter()
+        .collect();
+        let users: BTreeMap<u32, (u32, String, _)> = vec![(1, (1, "Alice".to_owned(), cells))]
+            .into_iter()
+            .collect();
+        let problems: BTreeSet<u32> = vec![101, 102].into_iter().collect();
+        let board = load_legacy(&(users, problems, at(0)), "first.cache");
+
+        let user = &lock(&board.user_map)[&1];
+        assert_eq!(user.name, "Alice");
+        assert_eq!(user.notes, None);
+        assert_eq!(user.last_submit, None);
+        let solved = user.problems[&101];
+        assert_eq!(
+            (solved.wa_count, solved.attempts, solved.solved_at),
+            (2, 3, None)
+        );
+        assert_eq!(user.problems[&102].attempts, 1);
+        assert_eq!(board.cache_time(), at(0));
+        assert!(lock(&board.feed).is_empty());
+    }
+
+    #[test]
+    fn first_cache_resyncs_across_its_cache_time_once() {
+        let old_cells: BTreeMap<u32, (usize, SolveStatus)> =
+            vec![(101, (2, SolveStatus::Accepted))]
+                .into_iter()
+                .collect();
+        let users: BTreeMap<u32, (u32, String, _)> = vec![(1, (1, "Alice".to_owned(), old_cells))]
+            .into_iter()
+            .collect();
+        let problems: BTreeSet<u32> = vec![101].into_iter().collect();
+        let board = Arc::new(load_legacy(&(users, problems, at(0)), "first-resync.cache"));
+
+        let list = vec![
+            // Counted before the upgrade, as Alice's second wrong try and AC
+            sub(1, 1, 101, Verdict::WA, -3),
+            sub(2, 1, 101, Verdict::AC, -1),
+            // Inside the look-back on a cell the old board never had
+            sub(3, 2, 101, Verdict::WA, -2),
+            sub(4, 1, 102, Verdict::WA, 10),
+        ];
+        save(&board, list.clone());
+        let after = cells(&board);
+        assert_eq!(
+            (after[&(1, 101)].wa_count, after[&(1, 101)].attempts),
+            (2, 3)
+        );
+        assert_eq!(after[&(2, 101)].wa_count, 1);
+        assert_eq!(after[&(1, 102)].wa_count, 1);
+        assert_eq!(lock(&board.feed).len(), 2);
+
+        // The first batch set the id watermark
+        save(&board, list);
+        assert_eq!(cells(&board), after);
+    }
+
+    #[test]
+    fn other_cache_layouts_fail_to_load() {
+        // A user list alone is no layout a release has written
+        let users: BTreeMap<u32, (u32, String)> =
+            vec![(7, (7, "Bob".to_owned()))].into_iter().collect();
+        let path = std::env::temp_dir().join(format!("{}-other.cache", std::process::id()));
+        fs::write(&path, bincode::serialize(&users).unwrap()).unwrap();
+        let board = Scoreboard::load_cache(&path);
+        let _ = fs::remove_file(&path);
+        assert!(board.is_err());
+    }
+
+    #[test]
+    fn revision_moves_only_when_a_sync_changes_cells() {
+        let subs = vec![
+            sub(1, 7, 101, Verdict::WA, 0),
+            sub(2, 7, 101, Verdict::Pending, 10),
+        ];
+        let board = board_of(subs.clone());
+        let first = board.revision();
+        assert_ne!(first, 0);
+
+        // The same list again, the pending one still unjudged
+        save(&board, subs);
+        assert_eq!(board.revision(), first);
+
+        save(
+            &board,
+            vec![
+                sub(1, 7, 101, Verdict::WA, 0),
+                sub(2, 7, 101, Verdict::AC, 10),
+            ],
+        );
+        assert_ne!(board.revision(), first);
+    }
+
+    #[test]
+    fn percent_scores_rank_on_the_normalized_sum() {
+        let scored = |id, uid, pid, score| Submission {
+            score: Some(score),
+            ..sub(id, uid, pid, Verdict::WA, id as i64)
+        };
+        // Alice has half of a 300 point problem, Bob 90% of a 100 point one
+        let board = board_of(vec![scored(1, 1, 101, 150), scored(2, 2, 102, 90)]);
+        name(&board, 1, "Alice");
+        name(&board, 2, "Bob");
+        let opts = TableOptions::default();
+
+        let absolute = meta("sort_mode = \"score\"\n[max_scores]\n101 = 300");
+        let table = board.gen_table(&absolute, &opts);
+        assert_eq!(row_names(&table), vec!["Alice", "Bob"]);
+        assert_eq!(rows(&table)["Alice"], vec!["150", "NS", "150"]);
+
+        let percent =
+            meta("sort_mode = \"score\"\nscore_display = \"percent\"\n[max_scores]\n101 = 300");
+        let table = board.gen_table(&percent, &opts);
+        assert_eq!(row_names(&table), vec!["Bob", "Alice"]);
+        let rows = rows(&table);
+        assert_eq!(rows["Alice"], vec!["50%", "NS", "50"]);
+        assert_eq!(rows["Bob"], vec!["NS", "90%", "90"]);
+    }
+
+    #[test]
+    fn cache_time_advances_to_the_last_judged_submission() {
+        let board = board_of(vec![
+            sub(1, 7, 101, Verdict::WA, 10),
+            sub(2, 8, 101, Verdict::AC, 30),
+            sub(3, 7, 102, Verdict::Pending, 50),
+        ]);
+        // The one still being judged has to be seen again
+        assert_eq!(board.cache_time(), at(30));
+
+        save(&board, vec![sub(3, 7, 102, Verdict::AC, 50)]);
+        assert_eq!(board.cache_time(), at(50));
+    }
+
+    #[test]
+    fn saving_overlapping_submissions_twice_changes_nothing() {
+        let subs = vec![
+            sub(1, 7, 101, Verdict::WA, 0),
+            sub(2, 7, 101, Verdict::AC, 10),
+            sub(3, 8, 101, Verdict::WA, 20),
+        ];
+        let board = board_of(subs.clone());
+        let before = (cells(&board), board.cache_time(), lock(&board.feed).len());
+
+        let mut again = subs;
+        again.push(sub(4, 8, 102, Verdict::WA, 30));
+        save(&board, again.clone());
+        save(&board, again);
+        let after = cells(&board);
+        assert_eq!(after[&(7, 101)], before.0[&(7, 101)]);
+        assert_eq!(after[&(8, 101)], before.0[&(8, 101)]);
+        assert_eq!(after[&(8, 102)].wa_count, 1);
+        assert_eq!(lock(&board.feed).len(), before.2 + 1);
+        assert!(board.cache_time() > before.1);
+    }
+
+    #[test]
+    fn unsorted_submissions_count_as_sorted_ones() {
+        let subs = vec![
+            sub(1, 7, 101, Verdict::WA, 0),
+            sub(2, 7, 101, Verdict::WA, 10),
+            sub(3, 7, 101, Verdict::AC, 20),
+            sub(4, 7, 101, Verdict::WA, 30),
+            sub(5, 8, 101, Verdict::AC, 40),
+        ];
+        let sorted = board_of(subs.clone());
+        let mut shuffled = subs;
+        shuffled.reverse();
+        shuffled.swap(1, 3);
+        let unsorted = board_of(shuffled);
+
+        assert_eq!(cells(&unsorted), cells(&sorted));
+        let cell = cells(&unsorted)[&(7, 101)];
+        assert_eq!((cell.wa_count, cell.solved_at), (2, Some(at(20))));
+        assert_eq!(unsorted.cache_time(), sorted.cache_time());
+        assert_eq!(lock(&unsorted.first_solver)[&101].user_id, 7);
+    }
+
+    #[test]
+    fn boundary_second_is_counted_once_across_two_syncs() {
+        // Three submissions share the second at 20, one is still being judged
+        let first = vec![
+            sub(1, 7, 101, Verdict::AC, 10),
+            sub(2, 8, 101, Verdict::WA, 20),
+            sub(3, 9, 101, Verdict::Pending, 20),
+            sub(4, 10, 101, Verdict::WA, 20),
+        ];
+        let board = board_of(first);
+        assert_eq!(*read(&board.seen_through), Some(2));
+        assert_eq!(*lock(&board.boundary_ids), [4].iter().copied().collect());
+
+        // It is judged, and another one arrives within the same second
+        let second = vec![
+            sub(1, 7, 101, Verdict::AC, 10),
+            sub(2, 8, 101, Verdict::WA, 20),
+            sub(3, 9, 101, Verdict::WA, 20),
+            sub(4, 10, 101, Verdict::WA, 20),
+            sub(5, 11, 101, Verdict::WA, 20),
+        ];
+        save(&board, second);
+        let cells = cells(&board);
+        for uid in 8..=11 {
+            let cell = cells[&(uid, 101)];
+            assert_eq!((cell.wa_count, cell.attempts), (1, 1), "user {}", uid);
+            assert!(!cell.pending);
+        }
+        assert_eq!(cells[&(7, 101)].attempts, 1);
+        assert_eq!(*read(&board.seen_through), Some(5));
+        assert!(lock(&board.boundary_ids).is_empty());
+    }
+
+    #[test]
+    fn public_export_keeps_the_exported_rows() {
+        let board = board_of(vec![
+            sub(1, 9, 101, Verdict::AC, 0),
+            sub(2, 1, 101, Verdict::AC, 10),
+            sub(3, 2, 101, Verdict::AC, 20),
+            sub(4, 3, 101, Verdict::WA, 30),
+        ]);
+        let meta = meta("min_ac = 1\nexclude_users = [9]\npseudonym_salt = \"salt\"");
+        // The first column below the header
+        let firsts = |csv: &str| -> Vec<String> {
+            csv.lines()
+                .skip(1)
+                .map(|line| line.split(',').next().unwrap().to_owned())
+                .collect()
+        };
+        let pseudonym = meta.pseudonyms().unwrap();
+
+        let public = board.export_public(ReportFormat::Csv, &meta).unwrap();
+        let ranks: Vec<String> = firsts(&public);
+        let exported = board.export_csv(&meta, &TableOptions::default());
+        assert_eq!(ranks, firsts(&exported));
+        assert_eq!(ranks, vec!["1", "2"]);
+        assert!(public.contains(&pseudonym(1)) && !public.contains(&pseudonym(9)));
+
+        let map = board.pseudonym_map(&meta).unwrap();
+        assert_eq!(firsts(&map), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn allowlisted_judge_verdicts_count_as_solves() {
+        let meta = meta("accepted_verdicts = [10, 12]");
+        let board = Arc::new(Scoreboard::new());
+        save_submissions(
+            board.clone(),
+            vec![
+                sub(1, 7, 101, Verdict::WA, 0),
+                sub(2, 7, 101, Verdict::Other(12), 10),
+                sub(3, 8, 101, Verdict::Other(13), 20),
+            ],
+            Duration::seconds(5),
+            &meta.accepted_verdicts(),
+            &meta.rejected_verdicts(),
+        )
+        .unwrap();
+        let cells = cells(&board);
+        assert_eq!(cells[&(7, 101)].status, SolveStatus::Accepted);
+        assert_eq!(cells[&(7, 101)].attempts, 2);
+        assert_ne!(cells[&(8, 101)].status, SolveStatus::Accepted);
+    }
+
+    #[test]
+    fn striped_rows_keep_the_ns_color() {
+        use crate::fake_term::FakeTerm;
+        use cursive::theme::{BaseColor, Color, ColorType};
+        use term::Terminal as _;
+
+        // User 1 leaves 102 unsolved on the first row, user 2 101 on the striped one
+        let board = board_of(vec![
+            sub(1, 1, 101, Verdict::AC, 0),
+            sub(2, 2, 102, Verdict::AC, 10),
+        ]);
+        let meta = meta("stripe_rows = true\n[theme]\nstripe = \"blue\"");
+        let mut term = FakeTerm::new();
+        board
+            .gen_table(&meta, &TableOptions::default())
+            .print_term(&mut term)
+            .unwrap();
+        let text = term.into_inner();
+        let ns: Vec<_> = text
+            .as_ref()
+            .spans()
+            .filter(|span| span.content.trim() == "NS")
+            .map(|span| span.attr.color.unwrap())
+            .collect();
+        let gray = ColorType::Color(Color::Light(BaseColor::Black));
+        let blue = ColorType::Color(Color::Dark(BaseColor::Blue));
+        assert_eq!(ns.len(), 2);
+        assert!(ns.iter().all(|style| style.front == gray));
+        assert_ne!(ns[0].back, blue);
+        assert_eq!(ns[1].back, blue);
+    }
+
+    #[test]
+    fn rows_map_back_to_their_users() {
+        let board = board_of(vec![
+            sub(1, 7, 101, Verdict::AC, 0),
+            sub(2, 8, 101, Verdict::WA, 10),
+            sub(3, 9, 101, Verdict::AC, 20),
+        ]);
+        let grouped = "[[problem_groups]]\nname = \"A\"\nproblems = [101]";
+        for meta in &[meta(""), meta(grouped)] {
+            let table = board.gen_table(meta, &TableOptions::default());
+            for (row, cells) in table.row_iter().enumerate() {
+                let uid = board.user_at_row(meta, &TableOptions::default(), row);
+                let name = cells.get_cell(0).unwrap().get_content();
+                assert_eq!(
+                    uid.map(|uid| format!("User #{}", uid)),
+                    Some(name).filter(|n| n.starts_with("User"))
+                );
+            }
+        }
+
+        let meta = meta("");
+        let table = board.gen_problem_table(101, &meta);
+        let names: Vec<String> = (0..table.len())
+            .filter_map(|row| board.problem_user_at_row(101, &meta, row))
+            .map(|uid| format!("User #{}", uid))
+            .collect();
+        assert_eq!(names, row_names(&table));
+    }
+
+    #[test]
+    fn ac_since_takes_only_solves_after_the_cutoff() {
+        let board = board_of(vec![
+            sub(1, 1, 101, Verdict::AC, 90),
+            sub(2, 1, 102, Verdict::AC, 110),
+            sub(3, 2, 101, Verdict::AC, 100),
+            sub(4, 2, 102, Verdict::WA, 120),
+            sub(5, 3, 103, Verdict::AC, 130),
+        ]);
+        let solved = board.ac_since(at(100));
+        // The solve at the cutoff itself was already reported
+        let expected: BTreeMap<u32, Vec<u32>> =
+            vec![(1, vec![102]), (3, vec![103])].into_iter().collect();
+        assert_eq!(solved, expected);
+        assert!(board.ac_since(at(130)).is_empty());
+    }
+
+    #[test]
+    fn gen_table_structure() {
+        // User 9 only tried a problem left off the board
+        let board = board_of(vec![
+            sub(1, 7, 101, Verdict::AC, 0),
+            sub(2, 8, 102, Verdict::WA, 10),
+            sub(3, 9, 103, Verdict::AC, 20),
+        ]);
+        let cells_of = |row: &Row| row.iter().map(|c| c.get_content()).collect::<Vec<_>>();
+        let header = vec!["", "101", "102"];
+
+        let footer = meta("exclude_problems = [103]\nshow_footer_header = true");
+        let table = board.gen_table(&footer, &TableOptions::default());
+        // The header, the update time, two users and the footer
+        assert_eq!(table.len(), 5);
+        assert_eq!(cells_of(table.get_row(0).unwrap()), header);
+        assert_eq!(cells_of(table.get_row(1).unwrap())[0], "Updated At");
+        assert_eq!(cells_of(table.get_row(4).unwrap()), header);
+        assert_eq!(row_names(&table), vec!["User #7", "User #8"]);
+        assert_eq!(rows(&table)["User #8"], vec!["NS", "WA / 1"]);
+
+        // Short boards leave the footer out unless asked
+        let table = board.gen_table(&meta("exclude_problems = [103]"), &TableOptions::default());
+        assert_eq!(table.len(), 4);
+
+        // Without an AC, a user only shows when min_ac lets them
+        let table = board.gen_table(
+            &meta("exclude_problems = [103]\nmin_ac = 1"),
+            &TableOptions::default(),
+        );
+        assert_eq!(row_names(&table), vec!["User #7"]);
+    }
+
+    #[test]
+    fn a_second_ac_counts_as_an_attempt_not_a_solve() {
+        let board = board_of(vec![
+            sub(1, 7, 101, Verdict::AC, 0),
+            sub(2, 7, 101, Verdict::AC, 60),
+            sub(3, 8, 101, Verdict::WA, 90),
+        ]);
+        assert_eq!(board.solve_count(101), 1);
+        assert_eq!(board.attempt_count(101), 3);
+        let cell = cells(&board)[&(7, 101)];
+        assert_eq!((cell.attempts, cell.wa_count), (2, 0));
+        // Solved by the first one
+        assert_eq!(cell.solved_at, Some(at(0)));
+        assert_eq!(lock(&board.feed).len(), 2);
+    }
+
+    #[test]
+    fn try_count_shows_tries_or_rejections_before_the_ac() {
+        let board = board_of(vec![
+            sub(1, 7, 101, Verdict::WA, 0),
+            sub(2, 7, 101, Verdict::WA, 60),
+            sub(3, 7, 101, Verdict::AC, 120),
+            sub(4, 7, 102, Verdict::WA, 180),
+        ]);
+        let tries = rows(&board.gen_table(&meta(""), &TableOptions::default()));
+        assert_eq!(tries["User #7"][..2], ["AC / 3 ★", "WA / 1"]);
+        let before =
+            rows(&board.gen_table(&meta("try_count = \"before\""), &TableOptions::default()));
+        assert_eq!(before["User #7"][..2], ["AC / 2 ★", "WA / 0"]);
+    }
+
+    #[test]
+    fn empty_name_reply_keeps_the_old_name_and_stops_asking() {
+        let board = board_of(vec![
+            sub(1, 7, 101, Verdict::WA, 0),
+            sub(2, 8, 101, Verdict::WA, 10),
+            sub(3, 9, 101, Verdict::WA, 20),
+        ]);
+        name(&board, 7, "Alice");
+        let names: BTreeMap<u32, String> = vec![(7, "".to_owned()), (8, " ".to_owned())]
+            .into_iter()
+            .collect();
+        assert_eq!(names_to_update(&board), vec![8, 9]);
+        save_names(&board, &[7, 8, 9], names);
+
+        let users = lock(&board.user_map);
+        assert_eq!(users[&7].name, "Alice");
+        assert!(users.values().all(|user| user.name_missing));
+        drop(users);
+        let table = board.gen_table(&meta(""), &TableOptions::default());
+        assert_eq!(row_names(&table), vec!["Alice", "User #8", "User #9"]);
+
+        // Once marked, the next refresh leaves them out of the lookup
+        assert!(names_to_update(&board).is_empty());
+    }
+
+    #[test]
+    fn two_groups_sharing_users_merge_into_one_board() {
+        let group_a = vec![
+            sub(1, 7, 101, Verdict::WA, 0),
+            sub(3, 8, 101, Verdict::AC, 20),
+            sub(4, 7, 101, Verdict::AC, 30),
+        ];
+        // User 7 is in both, so group B lists some of the same submissions
+        let group_b = vec![
+            sub(5, 9, 102, Verdict::AC, 40),
+            sub(4, 7, 101, Verdict::AC, 30),
+            sub(2, 7, 102, Verdict::WA, 10),
+            sub(1, 7, 101, Verdict::WA, 0),
+        ];
+        let (count, merged) = merge_lists(
+            vec![(3, group_a), (4, group_b)],
+            None,
+            TimeWindow::default(),
+        );
+        assert_eq!(
+            count,
+            FetchCount {
+                reported: 7,
+                received: 7
+            }
+        );
+        let ids: Vec<u64> = merged.iter().map(|sub| sub.id).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4, 5]);
+
+        let board = board_of(merged);
+        let cells = cells(&board);
+        assert_eq!(cells[&(7, 101)].wa_count, 1);
+        assert_eq!(cells[&(7, 101)].status, SolveStatus::Accepted);
+        assert_eq!(cells[&(7, 102)].wa_count, 1);
+        assert_eq!(cells[&(8, 101)].status, SolveStatus::Accepted);
+        assert_eq!(cells[&(9, 102)].status, SolveStatus::Accepted);
+        assert_eq!(board.attempt_count(101), 3);
+    }
+
+    #[test]
+    fn namespaced_groups_keep_a_shared_id_apart() {
+        let namespaced = meta("extra_groups = [2]\nnamespace_problems = true");
+        let columns = namespaced.problem_columns().unwrap();
+        let group_a = vec![sub(1, 7, 101, Verdict::AC, 0)];
+        // Listed by both groups, so counted once in each column
+        let group_b = vec![
+            sub(1, 7, 101, Verdict::AC, 0),
+            sub(2, 8, 101, Verdict::WA, 10),
+        ];
+        let (_, merged) = merge_lists(
+            vec![
+                (1, to_columns(&columns, 1, group_a)),
+                (2, to_columns(&columns, 2, group_b)),
+            ],
+            None,
+            TimeWindow::default(),
+        );
+        let board = board_of(merged);
+        let (a, b) = (
+            columns.column(1, 101).unwrap(),
+            columns.column(2, 101).unwrap(),
+        );
+        assert_eq!(board.displayed_problems(&namespaced), vec![a, b]);
+        assert_eq!(board.solve_count(a), 1);
+        assert_eq!(board.solve_count(b), 1);
+        assert_eq!(board.attempt_count(b), 2);
+
+        let table = board.gen_table(&namespaced, &TableOptions::default());
+        let header: Vec<String> = table
+            .get_row(0)
+            .unwrap()
+            .iter()
+            .map(|c| c.get_content())
+            .collect();
+        assert!(header.contains(&"G1/101".to_owned()));
+        assert!(header.contains(&"G2/101".to_owned()));
+        let csv = board.export_csv(&namespaced, &TableOptions::default());
+        assert!(csv.starts_with("rank,user_id,name,G1/101,G1/101_wa,G2/101,G2/101_wa"));
+
+        let compare = board.gen_compare_table(7, 8, &namespaced).to_string();
+        assert!(compare.contains("G1/101") && compare.contains("G2/101"));
+        // Excluded by its judge id, in every group
+        let excluded =
+            meta("extra_groups = [2]\nnamespace_problems = true\nexclude_problems = [101]");
+        let compare = board.gen_compare_table(7, 8, &excluded);
+        assert!(!compare.to_string().contains("/101"), "{}", compare);
+        let solved = compare.row_iter().last().unwrap();
+        assert_eq!(solved.get_cell(1).unwrap().get_content(), "0");
+    }
+
+    #[test]
+    fn wide_and_combining_names_keep_the_columns_aligned() {
+        use unicode_width::UnicodeWidthStr;
+
+        let board = board_of(vec![
+            sub(1, 7, 101, Verdict::AC, 0),
+            sub(2, 8, 101, Verdict::WA, 10),
+            sub(3, 9, 101, Verdict::AC, 20),
+        ]);
+        name(&board, 7, "Alice");
+        name(&board, 8, "王小明");
+        name(&board, 9, "Jose\u{301}");
+        let text = board
+            .gen_table(&meta(""), &TableOptions::default())
+            .to_string();
+        // The display column of every separator on the user lines
+        let starts = |line: &str| -> Vec<usize> {
+            line.match_indices('|')
+                .map(|(i, _)| line[..i].width())
+                .collect()
+        };
+        let lines: Vec<&str> = text
+            .lines()
+            .filter(|line| ["Alice", "王小明", "Jose"].iter().any(|n| line.contains(n)))
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| starts(line) == starts(lines[0])));
+    }
+
+    fn large_board() -> Arc<Scoreboard> {
+        Scoreboard::synthetic(500, 200).unwrap()
+    }
+
+    #[test]
+    fn large_board_cells_follow_their_submissions() {
+        let board = large_board();
+        let table = board.gen_table(&meta(""), &TableOptions::default());
+        // The header, the update time, every user and the footer
+        assert_eq!(table.len(), 503);
+        let cells_of = |row: &Row| row.iter().map(|c| c.get_content()).collect::<Vec<_>>();
+        assert_eq!(
+            cells_of(table.get_row(0).unwrap()),
+            cells_of(table.get_row(502).unwrap())
+        );
+        let rows = rows(&table);
+        for pid in 1..=200 {
+            let first = (1..=500).find(|uid| (uid * 7 + pid * 3) % 5 >= 2);
+            for uid in 1..=500 {
+                let expected = match (uid * 7 + pid * 3) % 5 {
+                    0 => "NS".to_owned(),
+                    1 => "WA / 1".to_owned(),
+                    _ if Some(uid) == first => "AC / 2 ★".to_owned(),
+                    _ => "AC / 2".to_owned(),
+                };
+                let name = format!("user{}", uid);
+                assert_eq!(
+                    rows[&name][pid as usize - 1],
+                    expected,
+                    "{} on {}",
+                    name,
+                    pid
+                );
+            }
+        }
     }
 }
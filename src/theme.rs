@@ -0,0 +1,160 @@
+use crate::error::{SimpleError, SimpleResult};
+use cursive::theme::{Color as CursiveColor, Palette, PaletteColor, Theme};
+use prettytable::color::{self, Color};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto as _;
+
+/// Grays of the 256-color palette, the 16 named colors have none that keeps
+/// the dim `ns` text readable on a stripe
+const DARK_GRAY: Color = 236;
+const LIGHT_GRAY: Color = 254;
+
+/// Theme settings in meta.toml, every color falls back to the preset
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThemeConfig {
+    preset: Option<String>,
+    background: Option<String>,
+    primary: Option<String>,
+    ac: Option<String>,
+    wa: Option<String>,
+    ns: Option<String>,
+    pending: Option<String>,
+    partial: Option<String>,
+    attempted: Option<String>,
+    stripe: Option<String>,
+}
+
+impl ThemeConfig {
+    pub fn resolve(&self) -> SimpleResult<BoardTheme> {
+        let mut theme = match &self.preset {
+            Some(name) => BoardTheme::preset(name).ok_or_else(|| SimpleError::Custom {
+                message: format!("Unknown theme preset: {}", name),
+            })?,
+            None => BoardTheme::default(),
+        };
+        override_color(&mut theme.background, &self.background)?;
+        override_color(&mut theme.primary, &self.primary)?;
+        override_color(&mut theme.ac, &self.ac)?;
+        override_color(&mut theme.wa, &self.wa)?;
+        override_color(&mut theme.ns, &self.ns)?;
+        override_color(&mut theme.pending, &self.pending)?;
+        override_color(&mut theme.partial, &self.partial)?;
+        override_color(&mut theme.attempted, &self.attempted)?;
+        override_color(&mut theme.stripe, &self.stripe)?;
+        Ok(theme)
+    }
+}
+
+fn override_color(target: &mut Color, name: &Option<String>) -> SimpleResult<()> {
+    if let Some(name) = name {
+        *target = color_by_name(name).ok_or_else(|| SimpleError::Custom {
+            message: format!("Unknown theme color: {}", name),
+        })?;
+    }
+    Ok(())
+}
+
+/// The colors used by both the cursive views and the table cells
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BoardTheme {
+    pub background: Color,
+    pub primary: Color,
+    pub ac: Color,
+    pub wa: Color,
+    pub ns: Color,
+    /// Cells still being judged, or frozen
+    pub pending: Color,
+    /// Wrong answers which scored some points
+    pub partial: Color,
+    /// Tried without any judged verdict yet
+    pub attempted: Color,
+    /// The background of every other row with `stripe_rows`
+    pub stripe: Color,
+}
+
+impl BoardTheme {
+    pub fn preset(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "dark" => Some(Self::default()),
+            "light" => Some(Self {
+                background: color::BRIGHT_WHITE,
+                primary: color::BLACK,
+                ac: color::GREEN,
+                wa: color::RED,
+                ns: color::BRIGHT_BLACK,
+                pending: color::BLUE,
+                // Yellow would hardly show on white
+                partial: color::MAGENTA,
+                attempted: color::CYAN,
+                stripe: LIGHT_GRAY,
+            }),
+            // The usual 16-color mapping of the solarized palette
+            "solarized" => Some(Self {
+                background: color::BRIGHT_BLACK,
+                primary: color::BRIGHT_BLUE,
+                ac: color::GREEN,
+                wa: color::RED,
+                ns: color::BRIGHT_GREEN,
+                pending: color::BLUE,
+                partial: color::YELLOW,
+                attempted: color::YELLOW,
+                stripe: color::BLACK,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn cursive_theme(&self) -> Theme {
+        let mut palette = Palette::default();
+        palette[PaletteColor::Background] = to_cursive(self.background);
+        palette[PaletteColor::Primary] = to_cursive(self.primary);
+        palette[PaletteColor::View] = to_cursive(self.background);
+        palette[PaletteColor::Shadow] = to_cursive(color::BRIGHT_BLACK);
+        Theme {
+            shadow: false,
+            palette,
+            ..Theme::default()
+        }
+    }
+}
+
+impl Default for BoardTheme {
+    fn default() -> Self {
+        Self {
+            background: color::BLACK,
+            primary: color::WHITE,
+            ac: color::GREEN,
+            wa: color::RED,
+            ns: color::BRIGHT_BLACK,
+            pending: color::BLUE,
+            partial: color::YELLOW,
+            attempted: color::YELLOW,
+            stripe: DARK_GRAY,
+        }
+    }
+}
+
+fn to_cursive(c: Color) -> CursiveColor {
+    CursiveColor::from_256colors(c.try_into().unwrap_or(0))
+}
+
+/// Translate a color name like "red" or "bright_blue" to a terminal color
+pub fn color_by_name(name: &str) -> Option<Color> {
+    let name = name.to_ascii_lowercase();
+    let (bright, base) = match name.strip_prefix("bright_") {
+        Some(base) => (true, base),
+        None => (false, name.as_str()),
+    };
+    let c = match base {
+        "black" => color::BLACK,
+        "red" => color::RED,
+        "green" => color::GREEN,
+        "yellow" => color::YELLOW,
+        "blue" => color::BLUE,
+        "magenta" => color::MAGENTA,
+        "cyan" => color::CYAN,
+        "white" => color::WHITE,
+        _ => return None,
+    };
+    Some(if bright { c + 8 } else { c })
+}
@@ -0,0 +1,138 @@
+//! `FojApi` against a judge faked on localhost
+
+use foj_scoreboard::scoreboard::{fetch_submissions, SyncConfig};
+use foj_scoreboard::{FojApi, TimeWindow};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Serves `reply` for each request, recording the request lines and the
+/// cookie sent along
+fn mock_judge(reply: fn(&str) -> String) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let log = seen.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => break,
+            };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                if line.to_ascii_lowercase().starts_with("cookie:") {
+                    log.lock()
+                        .unwrap()
+                        .push(format!("cookie: {}", line[7..].trim()));
+                }
+                line.clear();
+            }
+            let path = request.split_whitespace().nth(1).unwrap_or("").to_owned();
+            log.lock().unwrap().push(path.clone());
+            let body = reply(&path);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+    (base_url, seen)
+}
+
+fn reply(path: &str) -> String {
+    if path.starts_with("/session/") {
+        return r#"{"msg": {"name": "alice", "email": "alice@example.com", "id": 7}}"#.to_owned();
+    }
+    if path.starts_with("/submissions/") || path.starts_with("/contests/") {
+        return r#"{"msg": {"count": 2, "submissions": [
+            {"memory_usage": 1024, "time_usage": 12, "length": 120, "verdict_id": 10,
+             "execute_id": 1, "user_id": 7, "problem_id": 101,
+             "created_at": "2020-09-13 12:30:00", "updated_at": "2020-09-13 12:30:05",
+             "id": 2, "score": 100},
+            {"memory_usage": null, "time_usage": null, "length": 98, "verdict_id": 6,
+             "execute_id": 1, "user_id": 7, "problem_id": 101,
+             "created_at": "2020-09-13 12:20:00", "updated_at": "2020-09-13 12:20:03",
+             "id": 1, "score": null}
+        ]}}"#
+            .to_owned();
+    }
+    if path.starts_with("/users/?ids=") {
+        return r#"{"msg": [{"name": "alice", "id": 7}, {"name": "bob", "id": 8}]}"#.to_owned();
+    }
+    if path.starts_with("/users/") {
+        return r#"{"msg": {"name": "alice", "id": 7}}"#.to_owned();
+    }
+    r#"{"msg": "not found"}"#.to_owned()
+}
+
+#[test]
+fn fetches_the_session_submissions_and_names() {
+    let (base_url, seen) = mock_judge(reply);
+    let foj = FojApi::new("secret".to_owned(), &base_url).unwrap();
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let session = runtime.block_on(foj.session()).unwrap();
+    assert_eq!((session.id, session.name.as_str()), (7, "alice"));
+
+    let subs = runtime.block_on(foj.get_submission_group(3)).unwrap();
+    let ids: Vec<u64> = subs.iter().map(|sub| sub.id).collect();
+    assert_eq!(ids, vec![2, 1]);
+    assert_eq!(subs[0].score, Some(100));
+
+    let name = runtime.block_on(foj.get_user_name(7)).unwrap();
+    assert_eq!(name, "alice");
+    let names = runtime.block_on(foj.get_user_names(&[7, 8])).unwrap();
+    let names: Vec<(u32, &str)> = names
+        .iter()
+        .map(|(&id, name)| (id, name.as_str()))
+        .collect();
+    assert_eq!(names, vec![(7, "alice"), (8, "bob")]);
+
+    let seen = seen.lock().unwrap();
+    assert!(seen.contains(&"cookie: token=secret".to_owned()));
+    assert!(seen
+        .iter()
+        .any(|line| line.starts_with("/submissions/?group_id=3&count=1000&page=1")));
+    assert!(seen.contains(&"/users/7/".to_owned()));
+    assert!(seen
+        .iter()
+        .any(|line| line.starts_with("/users/?ids=7%2C8")));
+}
+
+#[test]
+fn dumps_each_submission_of_the_groups_and_contest_once() {
+    let (base_url, seen) = mock_judge(reply);
+    let foj = FojApi::new("secret".to_owned(), &base_url).unwrap();
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let config = SyncConfig {
+        gids: vec![3, 4],
+        contest: Some(5),
+        problem: None,
+        overlap: chrono::Duration::seconds(5),
+        accepted: vec![10],
+        rejected: vec![6],
+        window: TimeWindow::default(),
+        columns: None,
+    };
+    // Every list holds the same two submissions
+    let subs = runtime.block_on(fetch_submissions(&foj, &config)).unwrap();
+    let ids: Vec<u64> = subs.iter().map(|sub| sub.id).collect();
+    assert_eq!(ids, vec![1, 2]);
+
+    let seen = seen.lock().unwrap();
+    for list in &[
+        "/submissions/?group_id=3&",
+        "/submissions/?group_id=4&",
+        "/contests/5/submissions/",
+    ] {
+        assert!(seen.iter().any(|line| line.starts_with(list)), "{}", list);
+    }
+}